@@ -1,4 +1,5 @@
 use anyhow::{bail, Context, Error};
+use serde::Serialize;
 use std::{fmt::Display, str::FromStr};
 use string_enum::StringEnum;
 
@@ -36,9 +37,20 @@ pub enum NodeArch {
 
     /// `x64`
     X64,
+
+    /// `universal`
+    Universal,
 }
 
 impl NodeArch {
+    /// Values of `process.arch` which can use a binary for this arch.
+    pub fn cpus(self) -> Vec<NodeArch> {
+        match self {
+            NodeArch::Universal => vec![NodeArch::X64, NodeArch::Arm64],
+            _ => vec![self],
+        }
+    }
+
     pub fn from_cpu(cpu: &str) -> Result<Self, Error> {
         match cpu {
             "x86_64" => Ok(NodeArch::X64),
@@ -99,7 +111,8 @@ impl NodePlatform {
 }
 
 //// https://github.com/napi-rs/napi-rs/blob/main/cli/src/parse-triple.ts
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct PlatformDetail {
     pub platform: NodePlatform,
     pub platform_arch_abi: String,
@@ -148,6 +161,25 @@ impl FromStr for PlatformDetail {
     }
 }
 
+impl PlatformDetail {
+    /// Value for the `libc` field of `package.json`.
+    ///
+    /// Package managers use it to select a package built for the C library of
+    /// the system, because `linux-x64-gnu` and `linux-x64-musl` have the same
+    /// `os` and `cpu`.
+    pub fn libc(&self) -> Option<&'static str> {
+        if self.platform != NodePlatform::Linux {
+            return None;
+        }
+
+        match self.abi.as_deref() {
+            Some(abi) if abi.starts_with("musl") => Some("musl"),
+            Some(abi) if abi.starts_with("gnu") => Some("glibc"),
+            _ => None,
+        }
+    }
+}
+
 impl Display for PlatformDetail {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         Display::fmt(&self.platform_arch_abi, f)
@@ -1,10 +1,15 @@
-use crate::util::cargo::{cargo_target_dir, get_default_cargo_target};
+use super::timings::{TimingInfoMessage, TimingReport};
+use crate::util::{
+    cargo::{cargo_target_dir, get_default_cargo_target, resolve_deps, select_plugin_crates},
+    config::load_config,
+};
 use anyhow::{bail, Error};
 use cargo_metadata::Message;
 use std::{
-    io::BufReader,
+    io::{BufRead, BufReader},
     path::PathBuf,
     process::{Command, Stdio},
+    time::Instant,
 };
 use structopt::StructOpt;
 use tracing::{debug, error, info, warn};
@@ -17,7 +22,7 @@ pub struct BaseCargoBuildCommand {
     #[structopt(long)]
     pub release: bool,
 
-    /// Build one crate
+    /// Build one crate. Defaults to all plugin crates in the workspace.
     #[structopt(long)]
     pub crate_name: Vec<String>,
 
@@ -25,6 +30,10 @@ pub struct BaseCargoBuildCommand {
     #[structopt(long)]
     pub all: bool,
 
+    /// Crates to skip.
+    #[structopt(long)]
+    pub exclude: Vec<String>,
+
     /// Target triple.
     #[structopt(long)]
     pub target: Option<String>,
@@ -32,42 +41,59 @@ pub struct BaseCargoBuildCommand {
     /// Flags to pass to cargo.
     #[structopt(long)]
     pub cargo_flags: Option<Vec<String>>,
+
+    /// Record the compile time of each crate, print the slowest crates and
+    /// save a report to `.swc/timings`.
+    ///
+    /// The slowest crates are compared with the previous report of the same
+    /// target and profile.
+    ///
+    /// This requires a nightly toolchain.
+    #[structopt(long)]
+    pub timings: bool,
 }
 
 impl BaseCargoBuildCommand {
     #[tracing::instrument(name = "cargo build", skip(self))]
-    fn run_sync(&self) -> Result<Vec<BuiltPlugin>, Error> {
+    fn run_sync(&self) -> Result<BuildOutput, Error> {
         let mut cdylibs = vec![];
+        let mut native_links = vec![];
+        let mut timings = vec![];
         let mut cmd = Command::new("cargo");
 
         cmd.stdout(Stdio::piped())
             .arg("build")
             .arg("--message-format=json-render-diagnostics");
 
+        if self.timings {
+            cmd.arg("-Z").arg("timings=html,json");
+        }
+
         if self.release {
             cmd.arg("--release");
         }
 
         if self.all {
             cmd.arg("--workspace");
-        }
 
-        for name in &self.crate_name {
-            cmd.arg("--package").arg(&name);
+            for name in &self.exclude {
+                cmd.arg("--exclude").arg(&name);
+            }
+        } else {
+            for (name, _) in select_plugin_crates(&self.crate_name, &self.exclude)? {
+                cmd.arg("--package").arg(&name);
+            }
         }
 
         if let Some(target) = &self.target {
             cmd.arg("--target").arg(target);
         }
 
-        for name in &self.crate_name {
-            cmd.arg("--package").arg(&name);
-        }
-
         if let Some(flags) = &self.cargo_flags {
             cmd.args(flags);
         }
 
+        let start = Instant::now();
         let mut cargo = cmd.spawn().unwrap();
 
         let target = match self.target.clone() {
@@ -76,8 +102,17 @@ impl BaseCargoBuildCommand {
         };
 
         let reader = BufReader::new(cargo.stdout.take().unwrap());
-        for message in Message::parse_stream(reader) {
-            let message = message?;
+        for line in reader.lines() {
+            let line = line?;
+
+            if self.timings {
+                if let Some(timing) = TimingInfoMessage::parse(&line) {
+                    timings.push(timing);
+                    continue;
+                }
+            }
+
+            let message = serde_json::from_str(&line).unwrap_or(Message::TextLine(line));
             match message {
                 Message::CompilerMessage(msg) => {
                     println!("{:?}", msg);
@@ -118,7 +153,18 @@ impl BaseCargoBuildCommand {
 
                     warn!("Unhandled artifact message: {:?}", artifact);
                 }
-                Message::BuildScriptExecuted(..) => {}
+                Message::BuildScriptExecuted(script) => {
+                    if script.linked_libs.is_empty() {
+                        continue;
+                    }
+
+                    native_links.push(NativeLinks {
+                        package_id: script.package_id.repr.clone(),
+                        package: package_name_of(&script.package_id.repr),
+                        libs: script.linked_libs.iter().map(|v| v.to_string()).collect(),
+                        search_paths: script.linked_paths.iter().map(|v| v.to_string()).collect(),
+                    });
+                }
                 Message::BuildFinished(finished) => {
                     if finished.success {
                         info!("`cargo build` successed")
@@ -137,10 +183,25 @@ impl BaseCargoBuildCommand {
 
         debug!("Built {:?}", cdylibs);
 
-        Ok(cdylibs)
+        let timings = if self.timings {
+            Some(TimingReport {
+                target,
+                release: self.release,
+                total: start.elapsed().as_secs_f64(),
+                units: timings,
+            })
+        } else {
+            None
+        };
+
+        Ok(BuildOutput {
+            plugins: cdylibs,
+            native_links,
+            timings,
+        })
     }
 
-    pub fn run(self) -> Result<Vec<BuiltPlugin>, Error> {
+    pub fn run(self) -> Result<BuildOutput, Error> {
         let target_dir = cargo_target_dir()?;
         let target_dir_str = target_dir.to_string_lossy();
         info!(
@@ -148,7 +209,111 @@ impl BaseCargoBuildCommand {
             "Building swc plugin using cargo"
         );
 
-        self.run_sync()
+        let output = self.run_sync()?;
+
+        let config = load_config()?;
+        let target = match self.target.clone() {
+            Some(v) => v,
+            None => get_default_cargo_target()?,
+        };
+        let crates = if self.all {
+            // `--all` builds crates which are not plugins, too.
+            select_plugin_crates(&[], &self.exclude).unwrap_or_default()
+        } else {
+            select_plugin_crates(&self.crate_name, &self.exclude)?
+        };
+
+        let mut error = false;
+        for (crate_name, _) in crates {
+            // Build scripts of build dependencies and proc macros don't link anything
+            // into the plugin.
+            let deps = resolve_deps(crate_name.clone(), Some(target.clone()))?;
+            let linked = output.native_links.iter().filter(|links| {
+                links.package_id == deps.root.id.repr
+                    || deps.packages.iter().any(|p| p.id.repr == links.package_id)
+            });
+
+            for links in linked {
+                warn!(
+                    crate_name = &*crate_name,
+                    package = &*links.package,
+                    search_paths = &*links.search_paths.join(", "),
+                    "Build script links native libraries: {}",
+                    links.libs.join(", ")
+                );
+
+                let allowed = match &config.allowed_native_libs {
+                    Some(v) => v,
+                    None => continue,
+                };
+                for lib in links.dynamic_libs() {
+                    if !allowed.iter().any(|v| v == lib) {
+                        error = true;
+                        error!(
+                            crate_name = &*crate_name,
+                            "`{}` links `{}`, which is not in `allowed-native-libs`",
+                            links.package,
+                            lib
+                        );
+                    }
+                }
+            }
+        }
+
+        if error {
+            bail!("plugin links native libraries which are not allowed")
+        }
+
+        if let Some(report) = &output.timings {
+            let prev = report.save()?;
+            report.print(prev.as_ref());
+        }
+
+        Ok(output)
+    }
+}
+
+/// `name version` from the id of a package.
+pub(super) fn package_name_of(id: &str) -> String {
+    id.splitn(3, ' ').take(2).collect::<Vec<_>>().join(" ")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildOutput {
+    pub plugins: Vec<BuiltPlugin>,
+    pub native_links: Vec<NativeLinks>,
+    /// `Some` if `--timings` is used.
+    pub timings: Option<TimingReport>,
+}
+
+/// Native libraries linked by a build script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NativeLinks {
+    pub package_id: String,
+    /// `name version` of the package which owns the build script.
+    pub package: String,
+    /// Values of `cargo:rustc-link-lib`, like `dylib=ssl`.
+    pub libs: Vec<String>,
+    /// Values of `cargo:rustc-link-search`.
+    pub search_paths: Vec<String>,
+}
+
+impl NativeLinks {
+    /// Names of libraries which are not linked statically.
+    pub fn dynamic_libs(&self) -> impl '_ + Iterator<Item = &str> {
+        self.libs.iter().filter_map(|lib| {
+            // `[KIND[:MODIFIERS]=]NAME[:RENAME]`
+            let (kind, name) = match lib.split_once('=') {
+                Some((kind, name)) => (kind.split(':').next().unwrap(), name),
+                None => ("dylib", &**lib),
+            };
+
+            if kind == "static" {
+                return None;
+            }
+
+            Some(name.split(':').next().unwrap())
+        })
     }
 }
 
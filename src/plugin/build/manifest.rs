@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+/// Suffix of build manifests. A build manifest is stored next to the binary,
+/// like `my_plugin.linux-x64-gnu.so.swc-build.json`.
+pub(in crate::plugin) const BUILD_MANIFEST_SUFFIX: &str = ".swc-build.json";
+
+/// Describes a binary built by `plugin build`.
+///
+/// `plugin package --artifacts-dir` uses this to find binaries even if the
+/// directory structure is changed, like when artifacts are collected from CI.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(in crate::plugin) struct BuildManifest {
+    #[serde(rename = "crate")]
+    pub crate_name: String,
+    /// Like `linux-x64-gnu`.
+    pub platform: String,
+    /// Rust target triple.
+    pub target: String,
+    /// File name of the binary, relative to the manifest.
+    pub binary: String,
+}
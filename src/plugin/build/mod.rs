@@ -1,14 +1,19 @@
-use self::cargo::BaseCargoBuildCommand;
+use self::{
+    cargo::BaseCargoBuildCommand,
+    manifest::{BuildManifest, BUILD_MANIFEST_SUFFIX},
+};
 use crate::util::{
     cargo::{get_default_cargo_target, swc_output_dir},
-    node::create_npm_package,
+    copy_or_link,
+    node::pack::create_npm_package,
 };
 use anyhow::{anyhow, bail, Context, Error};
 use indexmap::IndexSet;
 use rayon::prelude::*;
 use std::{
     env::current_dir,
-    fs::{copy, create_dir_all},
+    fs::{create_dir_all, write},
+    path::PathBuf,
     sync::Arc,
 };
 use structopt::StructOpt;
@@ -16,6 +21,8 @@ use swc_node_arch::PlatformDetail;
 use tracing::{debug, error, info};
 
 mod cargo;
+pub(super) mod manifest;
+mod timings;
 
 /// Build plugin packages.
 #[derive(Debug, StructOpt)]
@@ -26,6 +33,18 @@ pub struct BuildCommand {
     /// Create node package file named `plugin-name.platform.swc-pkg.tar.gz`.
     #[structopt(long)]
     pub package: bool,
+
+    /// Directory to store the built binaries and package files.
+    ///
+    /// Defaults to `.swc/build` in the cargo target directory for binaries,
+    /// and the current directory for package files.
+    #[structopt(long, parse(from_os_str))]
+    pub out_dir: Option<PathBuf>,
+
+    /// Create hard links instead of copying files. Files are copied if a hard
+    /// link cannot be created.
+    #[structopt(long)]
+    pub link: bool,
 }
 
 impl BuildCommand {
@@ -38,9 +57,13 @@ impl BuildCommand {
         };
         let p: PlatformDetail = platform.parse().context("failed to parse platform")?;
 
-        let libs = self.cargo.run()?;
+        let link = self.link;
+        let libs = self.cargo.run()?.plugins;
 
-        let build_dir = Arc::new(output_base.join("build"));
+        let build_dir = Arc::new(match &self.out_dir {
+            Some(out_dir) => out_dir.clone(),
+            None => output_base.join("build"),
+        });
         let pkgs_dir = Arc::new(output_base.join("pkgs"));
         create_dir_all(&*build_dir)?;
 
@@ -59,14 +82,25 @@ impl BuildCommand {
                 );
                 let copied_path = build_dir.join(&name);
 
-                copy(&lib.cdylib_path, &copied_path).context("failed to copy file")?;
-
                 debug!(
                     "Copying {} to {}",
                     lib.cdylib_path.display(),
                     copied_path.display()
                 );
 
+                copy_or_link(&lib.cdylib_path, &copied_path, link)
+                    .context("failed to copy file")?;
+
+                let manifest = BuildManifest {
+                    crate_name: lib.crate_name.clone(),
+                    platform: p.platform_arch_abi.clone(),
+                    target: platform.clone(),
+                    binary: name.clone(),
+                };
+                let manifest_path = build_dir.join(format!("{}{}", name, BUILD_MANIFEST_SUFFIX));
+                write(&manifest_path, serde_json::to_string_pretty(&manifest)?)
+                    .with_context(|| format!("failed to write `{}`", manifest_path.display()))?;
+
                 Ok(())
             })
             .collect::<Vec<_>>();
@@ -91,33 +125,41 @@ impl BuildCommand {
 
         info!("Built files are copied to {}", build_dir.display());
 
-        let cur_dir = current_dir().context("failed to get current directory")?;
+        let pkg_file_dir = match &self.out_dir {
+            Some(out_dir) => out_dir.clone(),
+            None => current_dir().context("failed to get current directory")?,
+        };
 
         if self.package {
             for crate_name in crate_names.iter() {
+                let artifact = super::package::artifact_path(&build_dir, crate_name, &p);
                 let pkg_dir = super::package::create_package_for_platform(
                     &pkgs_dir,
-                    &build_dir,
+                    &artifact,
                     &crate_name,
                     &p,
                 )
                 .context("failed to create package for the built platform")?;
 
-                let pkg_file =
-                    create_npm_package(&pkg_dir).context("failed to create npm package")?;
-
-                info!("Created package file at `{}`", pkg_file.display());
+                let pkg_file = create_npm_package(&pkg_dir)
+                    .context("failed to create npm package")?
+                    .path;
 
                 let ext = pkg_file
                     .extension()
-                    .ok_or_else(|| {
-                        anyhow!("package file built by `npm pack` should have filename")
-                    })?
+                    .ok_or_else(|| anyhow!("npm package file should have extension"))?
                     .to_string_lossy();
                 let filename = format!("{}.{}.swc-pkg.{}", crate_name, p, ext);
 
-                copy(&pkg_file, &cur_dir.join(filename))
+                copy_or_link(&pkg_file, &pkg_file_dir.join(filename), link)
                     .context("failed to copy npm package file")?;
+
+                copy_or_link(
+                    &pkg_dir.join(super::package::CHECKSUMS_FILENAME),
+                    &super::package::checksums_path(&pkg_file_dir, crate_name, &p.to_string()),
+                    link,
+                )
+                .context("failed to copy checksums.txt")?;
             }
         }
 
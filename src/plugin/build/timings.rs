@@ -0,0 +1,250 @@
+use crate::util::{cargo::swc_output_dir, AHashMap};
+use anyhow::{Context, Error};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    fs::{create_dir_all, read_dir, read_to_string, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::info;
+
+/// Number of crates printed by [TimingReport::print].
+const SLOWEST_CRATES: usize = 15;
+
+/// `timing-info` message emitted by `cargo build -Z timings=json`.
+#[derive(Debug, Clone, Deserialize)]
+pub(super) struct TimingInfoMessage {
+    reason: String,
+    package_id: String,
+    target: TimingTarget,
+    #[serde(default)]
+    mode: String,
+    duration: f64,
+    #[serde(default)]
+    rmeta_time: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TimingTarget {
+    name: String,
+}
+
+impl TimingInfoMessage {
+    /// Parses a line of cargo output, if it's a `timing-info` message.
+    pub fn parse(line: &str) -> Option<UnitTiming> {
+        if !line.contains("\"timing-info\"") {
+            return None;
+        }
+
+        let msg: TimingInfoMessage = serde_json::from_str(line).ok()?;
+        if msg.reason != "timing-info" {
+            return None;
+        }
+
+        Some(UnitTiming {
+            package: super::cargo::package_name_of(&msg.package_id),
+            target: msg.target.name,
+            mode: msg.mode,
+            duration: msg.duration,
+            rmeta_time: msg.rmeta_time,
+        })
+    }
+}
+
+/// Time spent to compile an unit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnitTiming {
+    /// `name version`
+    pub package: String,
+    pub target: String,
+    pub mode: String,
+    /// In seconds.
+    pub duration: f64,
+    /// In seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rmeta_time: Option<f64>,
+}
+
+/// Stored in `.swc/timings` as json.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimingReport {
+    pub target: String,
+    pub release: bool,
+    /// Wall time of the whole build, in seconds.
+    pub total: f64,
+    pub units: Vec<UnitTiming>,
+}
+
+impl TimingReport {
+    /// Sum of the compile time of each crate, sorted by the time in descending
+    /// order.
+    fn by_crate(&self) -> Vec<(String, f64)> {
+        let mut map = AHashMap::<_, f64>::default();
+        for unit in &self.units {
+            *map.entry(unit.package.clone()).or_default() += unit.duration;
+        }
+
+        // `NaN` is sorted as zero, so that the order is total.
+        let mut v = map
+            .into_iter()
+            .map(|(name, d)| (name, if d.is_nan() { 0.0 } else { d }))
+            .collect::<Vec<_>>();
+        v.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        v
+    }
+
+    /// Prints the slowest crates, compared to `prev` if available.
+    pub fn print(&self, prev: Option<&TimingReport>) {
+        let prev = prev.map(|prev| prev.by_crate().into_iter().collect::<AHashMap<_, _>>());
+
+        println!(
+            "Slowest crates ({} units, {:.1}s total):",
+            self.units.len(),
+            self.total
+        );
+        for (name, duration) in self.by_crate().into_iter().take(SLOWEST_CRATES) {
+            match prev.as_ref().and_then(|prev| prev.get(&name)) {
+                Some(prev) => {
+                    println!("{:>8.2}s ({:+.2}s)  {}", duration, duration - prev, name)
+                }
+                None => println!("{:>8.2}s            {}", duration, name),
+            }
+        }
+    }
+
+    /// Saves the report to `.swc/timings/<target>/<profile>` and returns the
+    /// report of the previous run with the same target and profile, if any.
+    pub fn save(&self) -> Result<Option<TimingReport>, Error> {
+        let profile = if self.release { "release" } else { "debug" };
+        let dir = swc_output_dir()?
+            .join("timings")
+            .join(&self.target)
+            .join(profile);
+        create_dir_all(&dir).with_context(|| format!("failed to create `{}`", dir.display()))?;
+
+        let prev = latest_report_path(&dir)?
+            .map(|path| -> Result<_, Error> {
+                let s = read_to_string(&path)?;
+                Ok(serde_json::from_str(&s)?)
+            })
+            .transpose()
+            .context("failed to read the previous timing report")?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .context("system time is before the unix epoch")?
+            .as_millis();
+        let json = serde_json::to_string_pretty(self)?;
+
+        // Builds may finish at the same time, so the file is never overwritten.
+        let mut i = 0;
+        let path = loop {
+            let path = dir.join(format!("{}-{}-{}.json", timestamp, process::id(), i));
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    file.write_all(json.as_bytes()).with_context(|| {
+                        format!("failed to write timing report to `{}`", path.display())
+                    })?;
+                    break path;
+                }
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => i += 1,
+                Err(err) => {
+                    return Err(Error::new(err).context(format!(
+                        "failed to create timing report at `{}`",
+                        path.display()
+                    )))
+                }
+            }
+        };
+
+        info!("Timing report is saved at {}", path.display());
+
+        Ok(prev)
+    }
+}
+
+/// The report with the largest timestamp in `dir`.
+fn latest_report_path(dir: &Path) -> Result<Option<PathBuf>, Error> {
+    let mut paths = vec![];
+    for e in read_dir(dir)? {
+        let path = e?.path();
+        if path.extension().map_or(false, |ext| ext == "json") {
+            let timestamp = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.split('-').next())
+                .and_then(|s| s.parse::<u128>().ok());
+            if let Some(timestamp) = timestamp {
+                paths.push((timestamp, path));
+            }
+        }
+    }
+    paths.sort();
+
+    Ok(paths.pop().map(|(_, path)| path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+    use tempfile::TempDir;
+
+    fn unit(package: &str, duration: f64) -> UnitTiming {
+        UnitTiming {
+            package: package.into(),
+            target: package.into(),
+            mode: "build".into(),
+            duration,
+            rmeta_time: None,
+        }
+    }
+
+    #[test]
+    fn by_crate_with_nan() {
+        let report = TimingReport {
+            target: "x86_64-unknown-linux-gnu".into(),
+            release: false,
+            total: 3.0,
+            units: vec![
+                unit("a 1.0.0", 1.0),
+                unit("b 1.0.0", f64::NAN),
+                unit("c 1.0.0", 2.0),
+                unit("a 1.0.0", 2.0),
+            ],
+        };
+
+        let by_crate = report.by_crate();
+        assert_eq!(by_crate.len(), 3);
+        assert_eq!(by_crate[0], ("a 1.0.0".to_string(), 3.0));
+    }
+
+    #[test]
+    fn latest_report_by_timestamp() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(latest_report_path(dir.path()).unwrap(), None);
+
+        for name in &[
+            "900-1-0.json",
+            "1000-2-0.json",
+            "1000-1-1.json",
+            "notes.txt",
+        ] {
+            write(dir.path().join(name), "{}").unwrap();
+        }
+
+        assert_eq!(
+            latest_report_path(dir.path()).unwrap(),
+            Some(dir.path().join("1000-2-0.json"))
+        );
+    }
+}
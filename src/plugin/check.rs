@@ -0,0 +1,319 @@
+use crate::util::{
+    cargo::{cargo_workspace_root, has_cdylib, manifest_dir_of, select_plugin_crates_to_lint},
+    config::{load_config, Config},
+    node::validate::validate_package_json,
+};
+use anyhow::{bail, Context, Error};
+use cargo_metadata::{diagnostic::DiagnosticLevel, Message, Package};
+use serde_json::Value;
+use std::{
+    fs::read_to_string,
+    io::BufReader,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+use structopt::StructOpt;
+use tracing::{error, info, warn};
+
+/// Check plugin crates using `cargo check`, `cargo clippy` and lints for
+/// plugins, without building binaries.
+#[derive(Debug, StructOpt)]
+pub struct CheckCommand {
+    /// Crates to check. Defaults to all crates in the workspace which have
+    /// `package.json` or a `cdylib` target.
+    #[structopt(long)]
+    pub crates: Vec<String>,
+
+    /// Crates to skip.
+    #[structopt(long)]
+    pub exclude: Vec<String>,
+
+    /// Target triple.
+    #[structopt(long)]
+    pub target: Option<String>,
+
+    /// Skip `cargo clippy`.
+    #[structopt(long)]
+    pub no_clippy: bool,
+}
+
+impl CheckCommand {
+    pub fn run(self) -> Result<(), Error> {
+        let pkgs = select_plugin_crates_to_lint(&self.crates, &self.exclude)?;
+        if pkgs.is_empty() {
+            bail!("failed to find a plugin crate to check")
+        }
+
+        let mut failed = false;
+
+        let config = load_config()?;
+        let workspace_root = cargo_workspace_root()?;
+        for p in &pkgs {
+            for lint in lint_plugin_crate(p, &config, &workspace_root) {
+                failed = true;
+                error!(crate_name = &*p.name, "{}", lint);
+            }
+        }
+
+        let crate_names = pkgs.iter().map(|p| p.name.clone()).collect::<Vec<_>>();
+
+        if !self.run_cargo("check", &crate_names)? {
+            failed = true;
+        }
+
+        if !self.no_clippy && !self.run_cargo("clippy", &crate_names)? {
+            failed = true;
+        }
+
+        if failed {
+            bail!("checks for plugins failed")
+        }
+
+        info!("All checks passed");
+
+        Ok(())
+    }
+
+    /// Returns `false` if cargo failed or reported an error.
+    #[tracing::instrument(name = "cargo", skip(self, crate_names))]
+    fn run_cargo(&self, subcommand: &str, crate_names: &[String]) -> Result<bool, Error> {
+        let mut cmd = Command::new("cargo");
+
+        cmd.stdout(Stdio::piped())
+            .arg(subcommand)
+            .arg("--message-format=json")
+            .arg("--all-targets");
+
+        for name in crate_names {
+            cmd.arg("--package").arg(&name);
+        }
+
+        if let Some(target) = &self.target {
+            cmd.arg("--target").arg(target);
+        }
+
+        let mut cargo = cmd
+            .spawn()
+            .with_context(|| format!("failed to spawn `cargo {}`", subcommand))?;
+
+        let mut errors = 0;
+        let mut warnings = 0;
+
+        let reader = BufReader::new(cargo.stdout.take().unwrap());
+        for message in Message::parse_stream(reader) {
+            let message = message?;
+            if let Message::CompilerMessage(msg) = message {
+                match msg.message.level {
+                    DiagnosticLevel::Error | DiagnosticLevel::Ice => errors += 1,
+                    DiagnosticLevel::Warning => warnings += 1,
+                    _ => {}
+                }
+
+                if let Some(rendered) = &msg.message.rendered {
+                    eprint!("{}", rendered);
+                }
+            }
+        }
+
+        let status = cargo
+            .wait()
+            .with_context(|| format!("failed to wait for `cargo {}`", subcommand))?;
+
+        if warnings > 0 {
+            warn!("`cargo {}` reported {} warning(s)", subcommand, warnings);
+        }
+
+        if !status.success() || errors > 0 {
+            error!("`cargo {}` failed with {} error(s)", subcommand, errors);
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+}
+
+/// Lints specific to plugins. Returns a list of problems.
+fn lint_plugin_crate(p: &Package, config: &Config, workspace_root: &Path) -> Vec<String> {
+    let mut lints = vec![];
+    let manifest_dir = manifest_dir_of(p);
+
+    if !has_cdylib(p) {
+        lints.push(
+            "`crate-type` of the lib target should contain `cdylib`, as plugins are loaded as \
+             dynamic libraries"
+                .to_string(),
+        );
+    }
+
+    let package_json_path = manifest_dir.join("package.json");
+    if package_json_path.is_file() {
+        match read_package_json(&package_json_path) {
+            Ok(json) => {
+                lints.extend(
+                    validate_package_json(&json, &p.name, &config.naming)
+                        .into_iter()
+                        .map(|problem| format!("package.json: {}", problem)),
+                );
+
+                if let Some(version) = json.get("version").and_then(|v| v.as_str()) {
+                    if version != p.version.to_string() {
+                        lints.push(format!(
+                            "version of `package.json` ({}) differs from the version of \
+                             `Cargo.toml` ({})",
+                            version, p.version
+                        ));
+                    }
+                }
+            }
+            Err(err) => lints.push(format!("{:?}", err)),
+        }
+    } else {
+        lints.push(format!(
+            "`package.json` is required to publish the plugin, but `{}` does not exist",
+            package_json_path.display()
+        ));
+    }
+
+    match find_toolchain(&manifest_dir, workspace_root) {
+        Some((path, channel)) => {
+            if !is_pinned_toolchain(&channel) {
+                lints.push(format!(
+                    "toolchain `{}` in `{}` is not supported. Plugins should be built with a \
+                     toolchain pinned to a version or a date, like `nightly-2021-09-30`",
+                    channel,
+                    path.display()
+                ));
+            }
+        }
+        None => lints.push(
+            "failed to find `rust-toolchain`. Plugins should be built with a pinned toolchain"
+                .to_string(),
+        ),
+    }
+
+    lints
+}
+
+fn read_package_json(path: &Path) -> Result<Value, Error> {
+    let s = read_to_string(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+
+    serde_json::from_str(&s).with_context(|| format!("failed to parse `{}`", path.display()))
+}
+
+/// Finds `rust-toolchain` or `rust-toolchain.toml` from `dir` and its
+/// ancestors, up to `workspace_root`.
+///
+/// Returns `(path, channel)`.
+fn find_toolchain(dir: &Path, workspace_root: &Path) -> Option<(PathBuf, String)> {
+    for dir in dir.ancestors() {
+        for name in &["rust-toolchain", "rust-toolchain.toml"] {
+            let path = dir.join(name);
+            let s = match read_to_string(&path) {
+                Ok(s) => s,
+                Err(_) => continue,
+            };
+
+            let channel = s
+                .lines()
+                .map(|l| l.trim())
+                .find_map(|l| {
+                    if let Some(rest) = l.strip_prefix("channel") {
+                        let rest = rest.trim_start().strip_prefix('=')?;
+                        return Some(rest.trim().trim_matches('"').to_string());
+                    }
+
+                    if !l.is_empty()
+                        && !l.starts_with('[')
+                        && !l.starts_with('#')
+                        && !l.contains('=')
+                    {
+                        return Some(l.to_string());
+                    }
+
+                    None
+                })
+                .unwrap_or_default();
+
+            return Some((path, channel));
+        }
+
+        // Toolchain files outside of the workspace are not used by CI.
+        if dir == workspace_root {
+            break;
+        }
+    }
+
+    None
+}
+
+/// `stable`, `beta` and `nightly` are not pinned.
+fn is_pinned_toolchain(channel: &str) -> bool {
+    // `1.56.0`
+    if channel.starts_with(|c: char| c.is_ascii_digit()) {
+        return true;
+    }
+
+    // `nightly-2021-09-30`
+    channel.splitn(2, '-').nth(1).map_or(false, |date| {
+        date.len() >= 10
+            && date[..10].bytes().enumerate().all(|(i, b)| match i {
+                4 | 7 => b == b'-',
+                _ => b.is_ascii_digit(),
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, write};
+    use tempfile::TempDir;
+
+    #[test]
+    fn toolchain_in_workspace() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("workspace");
+        let crate_dir = root.join("plugins/plugin");
+        create_dir_all(&crate_dir).unwrap();
+
+        write(root.join("rust-toolchain"), "nightly-2021-09-30\n").unwrap();
+        assert_eq!(
+            find_toolchain(&crate_dir, &root),
+            Some((
+                root.join("rust-toolchain"),
+                "nightly-2021-09-30".to_string()
+            ))
+        );
+
+        write(
+            crate_dir.join("rust-toolchain.toml"),
+            "[toolchain]\nchannel = \"1.56.0\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            find_toolchain(&crate_dir, &root),
+            Some((crate_dir.join("rust-toolchain.toml"), "1.56.0".to_string()))
+        );
+    }
+
+    #[test]
+    fn toolchain_outside_of_workspace() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("workspace");
+        let crate_dir = root.join("plugin");
+        create_dir_all(&crate_dir).unwrap();
+
+        write(dir.path().join("rust-toolchain"), "nightly-2021-09-30\n").unwrap();
+        assert_eq!(find_toolchain(&crate_dir, &root), None);
+    }
+
+    #[test]
+    fn pinned_toolchains() {
+        for channel in &["1.56.0", "nightly-2021-09-30", "stable-2021-10-21"] {
+            assert!(is_pinned_toolchain(channel), "{}", channel);
+        }
+        for channel in &["", "stable", "nightly", "beta", "nightly-2021"] {
+            assert!(!is_pinned_toolchain(channel), "{}", channel);
+        }
+    }
+}
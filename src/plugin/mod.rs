@@ -1,11 +1,12 @@
 use self::{
-    build::BuildCommand, init::InitCommand, package::PackageCommand, publish::PublishDepsCommand,
-    upgrade_deps::UpgradeDepsCommand,
+    build::BuildCommand, check::CheckCommand, init::InitCommand, package::PackageCommand,
+    publish::PublishDepsCommand, upgrade_deps::UpgradeDepsCommand,
 };
 use anyhow::{Context, Error};
 use structopt::StructOpt;
 
 pub mod build;
+pub mod check;
 pub mod init;
 pub mod package;
 pub mod publish;
@@ -16,6 +17,7 @@ pub mod upgrade_deps;
 pub enum PluginCommand {
     Init(InitCommand),
     Build(BuildCommand),
+    Check(CheckCommand),
     Package(PackageCommand),
     PublishDeps(PublishDepsCommand),
     UpgradeDeps(UpgradeDepsCommand),
@@ -30,6 +32,9 @@ impl PluginCommand {
             PluginCommand::Build(cmd) => {
                 cmd.run()?;
             }
+            PluginCommand::Check(cmd) => {
+                cmd.run()?;
+            }
             PluginCommand::Package(cmd) => {
                 cmd.run()?;
             }
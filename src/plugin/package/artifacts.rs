@@ -0,0 +1,171 @@
+use super::artifact_path;
+use crate::{
+    plugin::build::manifest::{BuildManifest, BUILD_MANIFEST_SUFFIX},
+    util::AHashMap,
+};
+use anyhow::{Context, Error};
+use std::{
+    fs::{read_dir, read_to_string},
+    path::{Path, PathBuf},
+};
+use swc_node_arch::PlatformDetail;
+use tracing::{debug, warn};
+
+/// Default value of `--artifact-pattern`.
+pub(super) const DEFAULT_ARTIFACT_PATTERN: &str = "{crate}.{platform}.{ext}";
+
+/// Locates binaries to package.
+#[derive(Debug)]
+pub(super) struct Artifacts {
+    build_dir: PathBuf,
+    /// Binaries found in `--artifacts-dir`, by crate name and platform.
+    found: AHashMap<(String, String), PathBuf>,
+}
+
+impl Artifacts {
+    /// Uses binaries in `build_dir`, named by `plugin build`.
+    pub fn in_build_dir(build_dir: PathBuf) -> Self {
+        Artifacts {
+            build_dir,
+            found: Default::default(),
+        }
+    }
+
+    /// Searches `dir` recursively for binaries.
+    ///
+    /// Binaries are found by build manifests, or by file names matching
+    /// `pattern`. `{crate}`, `{platform}` and `{ext}` (`so`, `dylib` or `dll`)
+    /// in `pattern` are replaced. Binaries not found in `dir` are looked up
+    /// in `build_dir`.
+    pub fn find(
+        dir: &Path,
+        build_dir: PathBuf,
+        crate_names: &[String],
+        platforms: &[PlatformDetail],
+        pattern: &str,
+    ) -> Result<Self, Error> {
+        let mut files = vec![];
+        collect_files(dir, &mut files)
+            .with_context(|| format!("failed to read `{}`", dir.display()))?;
+        files.sort();
+
+        let mut found = AHashMap::default();
+
+        for path in &files {
+            let name = path.file_name().unwrap().to_string_lossy();
+            if !name.ends_with(BUILD_MANIFEST_SUFFIX) {
+                continue;
+            }
+
+            let s = read_to_string(path)
+                .with_context(|| format!("failed to read `{}`", path.display()))?;
+            let manifest: BuildManifest = serde_json::from_str(&s)
+                .with_context(|| format!("failed to parse `{}`", path.display()))?;
+            if !crate_names.contains(&manifest.crate_name) {
+                continue;
+            }
+
+            let bin_path = path.parent().unwrap().join(&manifest.binary);
+            if !bin_path.is_file() {
+                warn!(
+                    "`{}` refers to `{}`, which does not exist",
+                    path.display(),
+                    bin_path.display()
+                );
+                continue;
+            }
+
+            insert(&mut found, manifest.crate_name, manifest.platform, bin_path);
+        }
+
+        for crate_name in crate_names {
+            for platform in platforms {
+                let key = (crate_name.clone(), platform.to_string());
+                if found.contains_key(&key) {
+                    continue;
+                }
+
+                let expected = pattern
+                    .replace("{crate}", crate_name)
+                    .replace("{platform}", &platform.platform_arch_abi)
+                    .replace(
+                        "{ext}",
+                        platform.platform.cdylib_ext().trim_start_matches('.'),
+                    );
+                let mut matches = files
+                    .iter()
+                    .filter(|path| path.file_name().map_or(false, |name| name == &*expected));
+
+                if let Some(path) = matches.next() {
+                    if let Some(other) = matches.next() {
+                        warn!(
+                            "Both `{}` and `{}` match `{}`. Using the first one",
+                            path.display(),
+                            other.display(),
+                            expected
+                        );
+                    }
+                    insert(&mut found, key.0, key.1, path.clone());
+                }
+            }
+        }
+
+        Ok(Artifacts { build_dir, found })
+    }
+
+    /// Path of the binary of `crate_name` for `platform`. The file may not
+    /// exist.
+    pub fn path(&self, crate_name: &str, platform: &PlatformDetail) -> PathBuf {
+        match self
+            .found
+            .get(&(crate_name.to_string(), platform.to_string()))
+        {
+            Some(path) => path.clone(),
+            None => artifact_path(&self.build_dir, crate_name, platform),
+        }
+    }
+
+    /// Directory for binaries created by `plugin package`, like universal
+    /// binaries.
+    pub fn build_dir(&self) -> &Path {
+        &self.build_dir
+    }
+}
+
+fn insert(
+    found: &mut AHashMap<(String, String), PathBuf>,
+    crate_name: String,
+    platform: String,
+    path: PathBuf,
+) {
+    debug!(
+        "Found the binary of `{}` for `{}` at {}",
+        crate_name,
+        platform,
+        path.display()
+    );
+
+    if let Some(prev) = found.insert((crate_name.clone(), platform.clone()), path) {
+        warn!(
+            "Found multiple binaries of `{}` for `{}`. Ignoring `{}`",
+            crate_name,
+            platform,
+            prev.display()
+        );
+    }
+}
+
+fn collect_files(dir: &Path, buf: &mut Vec<PathBuf>) -> Result<(), Error> {
+    for e in read_dir(dir)? {
+        let e = e?;
+        let ty = e.file_type()?;
+
+        if ty.is_dir() {
+            collect_files(&e.path(), buf)?;
+        } else if ty.is_file() {
+            buf.push(e.path());
+        }
+    }
+
+    Ok(())
+}
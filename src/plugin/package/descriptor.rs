@@ -0,0 +1,63 @@
+use serde::Serialize;
+use swc_node_arch::PlatformDetail;
+
+pub(super) const DESCRIPTOR_FILENAME: &str = "swc-plugin.json";
+
+/// Version of the format of [PluginDescriptor].
+const SCHEMA_VERSION: u32 = 1;
+
+/// `swc-plugin.json`, which describes the binary in a platform package.
+///
+/// Tools can use this to check compatibility without loading the binary.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct PluginDescriptor {
+    schema_version: u32,
+    /// Name of the main npm package.
+    pub name: String,
+    #[serde(rename = "crate")]
+    pub crate_name: String,
+    /// Version of the crate.
+    pub version: String,
+    /// Rust target triples of the binary. An universal binary has two.
+    pub targets: Vec<String>,
+    pub platform: PlatformDetail,
+    /// File name of the binary in the package.
+    pub binary: String,
+    /// Resolved version of `swc_plugin`.
+    pub swc_plugin: Option<String>,
+    /// Resolved version of `swc_common`.
+    pub swc_common: Option<String>,
+    /// Resolved version of `abi_stable`, which determines the layout of types
+    /// shared with swc.
+    pub abi_version: Option<String>,
+    /// Hex-encoded sha512 of the binary. This is set when the package is
+    /// created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binary_sha512: Option<String>,
+}
+
+impl PluginDescriptor {
+    pub fn new(
+        name: String,
+        crate_name: String,
+        version: String,
+        targets: Vec<String>,
+        platform: PlatformDetail,
+        binary: String,
+    ) -> Self {
+        PluginDescriptor {
+            schema_version: SCHEMA_VERSION,
+            name,
+            crate_name,
+            version,
+            targets,
+            platform,
+            binary,
+            swc_plugin: None,
+            swc_common: None,
+            abi_version: None,
+            binary_sha512: None,
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use super::{
+    artifacts::Artifacts, create_notices, create_sbom, find_license_files, plan::describe_file,
+    plugin_descriptor, report::PackageStatus, swc_core_peer_range, write_checksums,
+    DESCRIPTOR_FILENAME, NOTICES_FILENAME, SBOM_FILENAME,
+};
+use crate::{
+    plugin::package::package_json::PackageJsonForBin,
+    util::{
+        binary::verify_binary,
+        cargo::{get_cargo_manifest_path, resolve_deps},
+        config::load_config,
+        node::{json_diff, pack::packed_files},
+    },
+};
+use anyhow::{Context, Error};
+use serde_json::Value;
+use sha2::{Digest, Sha512};
+use std::{
+    fs::{copy, create_dir_all, read, read_to_string, remove_dir_all, write},
+    path::{Path, PathBuf},
+};
+use swc_node_arch::{NodeArch, PlatformDetail};
+use tracing::{error, info, warn};
+
+/// Used instead of a platform in the name of the package file, like
+/// `my_plugin.all.swc-pkg.tgz`.
+pub(in crate::plugin) const FAT_PACKAGE_PLATFORM: &str = "all";
+
+/// A binary in the fat package.
+#[derive(Debug)]
+pub(super) struct FatBinary {
+    pub platform: PlatformDetail,
+    /// Directory in the package, like `bin/linux-x64-gnu`.
+    pub dir: String,
+    pub filename: String,
+    /// The binary built by `plugin build`.
+    pub artifact: PathBuf,
+}
+
+/// Contents of the fat package, computed by [plan_fat_package].
+#[derive(Debug)]
+pub(super) struct FatPackagePlan {
+    pub pkg_dir: PathBuf,
+    pub main_name: String,
+    pub version: String,
+    /// The main `package.json`.
+    pub main_package_json: Value,
+    pub package_json: PackageJsonForBin,
+    pub binaries: Vec<FatBinary>,
+    /// Files copied to the package directory, with their source.
+    pub copied: Vec<(String, PathBuf)>,
+    /// Files generated in the package directory, with their content.
+    ///
+    /// `README.md` and `package.json` are not included because `README.md`
+    /// depends on the binaries actually added.
+    pub generated: Vec<(String, String)>,
+}
+
+impl FatPackagePlan {
+    /// Prints the plan for `package --dry-run`.
+    ///
+    /// `universal_sources` are binaries merged into the binary for
+    /// `darwin-universal`, and `checksums` is where `checksums.txt` is copied
+    /// to. Returns the number of missing binaries.
+    pub fn print(&self, universal_sources: &[PathBuf], checksums: &Path) -> usize {
+        println!("{}", self.pkg_dir.display());
+
+        let mut included = vec![];
+        println!("  binaries:");
+        for bin in &self.binaries {
+            let sources = if bin.platform.arch == NodeArch::Universal {
+                universal_sources
+            } else {
+                &[]
+            };
+
+            println!(
+                "    {}/{}: {}",
+                bin.dir,
+                bin.filename,
+                describe_file(&bin.artifact, sources.is_empty())
+            );
+            for source in sources {
+                println!("      merged from: {}", describe_file(source, true));
+            }
+
+            let exists = if sources.is_empty() {
+                bin.artifact.is_file()
+            } else {
+                sources.iter().all(|p| p.is_file())
+            };
+            if exists {
+                included.push(bin);
+            }
+        }
+
+        println!("  package.json:");
+        let generated = serde_json::to_value(&self.package_json).unwrap_or(Value::Null);
+        for line in json_diff(&self.main_package_json, &generated) {
+            println!("    {}", line);
+        }
+
+        println!("  files:");
+        let mut names = self
+            .copied
+            .iter()
+            .map(|(name, _)| name.clone())
+            .chain(self.generated.iter().map(|(name, _)| name.clone()))
+            .collect::<Vec<_>>();
+        names.push("README.md".into());
+        names.push("package.json".into());
+        for bin in &included {
+            names.push(format!("{}/{}", bin.dir, bin.filename));
+            names.push(format!("{}/{}", bin.dir, DESCRIPTOR_FILENAME));
+        }
+        for name in packed_files(Some(&self.package_json.files), names) {
+            println!("    {}", name);
+        }
+        if included.is_empty() {
+            println!("  (skipped because no binary is built)");
+        } else {
+            println!("  checksums: {}", checksums.display());
+        }
+        println!();
+
+        self.binaries.len() - included.len()
+    }
+}
+
+/// Computes the contents of the fat package without writing anything.
+pub(super) fn plan_fat_package(
+    pkgs_dir: &Path,
+    artifacts: &Artifacts,
+    crate_name: &str,
+    platforms: &[PlatformDetail],
+) -> Result<FatPackagePlan, Error> {
+    let config = load_config()?;
+
+    let manifest_path = get_cargo_manifest_path(crate_name.to_string())
+        .context("failed to get the path of cargo manifest")?;
+    let manifest_dir = manifest_path.parent().unwrap();
+    let package_json_path = manifest_dir.join("package.json");
+    let package_json_str = read_to_string(&package_json_path)
+        .with_context(|| format!("failed to read `{}`", package_json_path.display()))?;
+    let main_package_json: Value = serde_json::from_str(&package_json_str)
+        .with_context(|| format!("failed to parse `{}`", package_json_path.display()))?;
+
+    let mut pkg_json = PackageJsonForBin::from_main(&package_json_str, &config.package_json)
+        .with_context(|| {
+            format!(
+                "failed to create the package.json file for the fat package from `{}`",
+                package_json_path.display()
+            )
+        })?;
+    let main_name = pkg_json.name.clone();
+    pkg_json.name = config.naming.fat_package_name(&main_name, crate_name);
+    pkg_json.description = format!(
+        "This package is part of {}. This package contains binaries for all platforms.",
+        main_name
+    );
+
+    let pkg_dir = pkgs_dir.join(format!("{}-{}", crate_name, FAT_PACKAGE_PLATFORM));
+
+    let binaries = platforms
+        .iter()
+        .map(|platform| FatBinary {
+            platform: platform.clone(),
+            dir: format!("bin/{}", platform),
+            filename: config
+                .naming
+                .binary_filename(&main_name, crate_name, platform),
+            artifact: artifacts.path(crate_name, platform),
+        })
+        .collect();
+    pkg_json.files.push("bin".into());
+
+    let mut copied = vec![];
+    let mut generated = vec![];
+
+    for path in find_license_files(manifest_dir)? {
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        pkg_json.files.push(name.clone());
+        copied.push((name, path));
+    }
+
+    pkg_json.files.push("README.md".into());
+
+    if let Some(range) = swc_core_peer_range(crate_name, &config)? {
+        pkg_json
+            .rest
+            .entry("peerDependencies")
+            .or_insert_with(|| Value::Object(Default::default()))
+            .as_object_mut()
+            .context("`peerDependencies` should be an object")?
+            .insert("@swc/core".to_string(), Value::String(range));
+    }
+
+    // Dependencies of all platforms.
+    let deps = resolve_deps(crate_name.to_string(), None)?;
+    generated.push((
+        SBOM_FILENAME.into(),
+        serde_json::to_string_pretty(&create_sbom(&deps, None))?,
+    ));
+    if config.include_sbom {
+        pkg_json.files.push(SBOM_FILENAME.into());
+    }
+
+    let notices = create_notices(&deps, config.allowed_licenses.as_deref()).with_context(|| {
+        format!(
+            "failed to collect licenses of crates linked into `{}`",
+            crate_name
+        )
+    })?;
+    generated.push((NOTICES_FILENAME.into(), notices));
+    pkg_json.files.push(NOTICES_FILENAME.into());
+
+    Ok(FatPackagePlan {
+        pkg_dir,
+        main_name,
+        version: deps.root.version.to_string(),
+        main_package_json,
+        package_json: pkg_json,
+        binaries,
+        copied,
+        generated,
+    })
+}
+
+/// Creates a package which contains binaries for all platforms in
+/// `bin/<platform>/`.
+///
+/// Returns the package directory and the status of each platform.
+#[tracing::instrument(name = "build_fat_package", skip(pkgs_dir, artifacts, platforms))]
+pub(super) fn create_fat_package(
+    pkgs_dir: &Path,
+    artifacts: &Artifacts,
+    crate_name: &str,
+    platforms: &[PlatformDetail],
+) -> Result<(PathBuf, Vec<PackageStatus>), Error> {
+    info!("Creating a package for all platforms");
+
+    let plan = plan_fat_package(pkgs_dir, artifacts, crate_name, platforms)?;
+    let pkg_dir = &plan.pkg_dir;
+
+    // Don't publish a package without binaries.
+    if plan.binaries.iter().all(|bin| !bin.artifact.is_file()) {
+        warn!("Skipping the package for all platforms because no binary is built");
+        return Ok((plan.pkg_dir, vec![PackageStatus::Missing; platforms.len()]));
+    }
+
+    let bin_dir = pkg_dir.join("bin");
+    // Remove binaries of previous runs.
+    if bin_dir.exists() {
+        remove_dir_all(&bin_dir)
+            .with_context(|| format!("failed to remove `{}`", bin_dir.display()))?;
+    }
+    create_dir_all(&pkg_dir)
+        .with_context(|| format!("failed to create `{}`", pkg_dir.display()))?;
+
+    let mut statuses = vec![];
+    let mut bin_files = vec![];
+    for FatBinary {
+        platform,
+        dir,
+        filename,
+        artifact,
+    } in &plan.binaries
+    {
+        if !artifact.is_file() {
+            statuses.push(PackageStatus::Missing);
+            continue;
+        }
+
+        let result = (|| -> Result<_, Error> {
+            let bin = read(&artifact)
+                .with_context(|| format!("failed to read `{}`", artifact.display()))?;
+            verify_binary(&bin, platform).with_context(|| {
+                format!(
+                    "`{}` is not a binary for `{}`",
+                    artifact.display(),
+                    platform
+                )
+            })?;
+
+            create_dir_all(pkg_dir.join(&dir))?;
+            write(pkg_dir.join(&dir).join(&filename), &bin)?;
+
+            let mut descriptor = plugin_descriptor(
+                crate_name,
+                &plan.main_name,
+                &plan.version,
+                platform,
+                filename,
+            )?;
+            descriptor.binary_sha512 = Some(hex::encode(Sha512::digest(&bin)));
+            write(
+                pkg_dir.join(&dir).join(DESCRIPTOR_FILENAME),
+                serde_json::to_string_pretty(&descriptor)?,
+            )?;
+
+            Ok(vec![
+                format!("{}/{}", dir, filename),
+                format!("{}/{}", dir, DESCRIPTOR_FILENAME),
+            ])
+        })();
+
+        match result {
+            Ok(files) => {
+                bin_files.extend(files);
+                statuses.push(PackageStatus::Packaged);
+            }
+            Err(err) => {
+                error!(
+                    "failed to add the binary for `{}` to the package: {:?}",
+                    platform, err
+                );
+                statuses.push(PackageStatus::Failed);
+            }
+        }
+    }
+
+    for (name, from) in &plan.copied {
+        copy(from, pkg_dir.join(name))
+            .with_context(|| format!("failed to copy `{}`", from.display()))?;
+    }
+
+    let packaged = platforms
+        .iter()
+        .zip(&statuses)
+        .filter(|(_, s)| **s == PackageStatus::Packaged)
+        .map(|(p, _)| format!("- `{}`", p))
+        .collect::<Vec<_>>();
+    let readme = [
+        format!("# `{}`", plan.package_json.name),
+        String::new(),
+        format!(
+            "This package contains the binaries of [`{}`](https://www.npmjs.com/package/{}) for \
+             all platforms.",
+            plan.main_name, plan.main_name
+        ),
+        format!(
+            "It's installed by `{}`, so you don't need to install it directly.",
+            plan.main_name
+        ),
+        String::new(),
+        packaged.join("\n"),
+        String::new(),
+    ]
+    .join("\n");
+    write(pkg_dir.join("README.md"), readme).context("failed to write README.md")?;
+
+    for (name, content) in &plan.generated {
+        let path = pkg_dir.join(name);
+        write(&path, content).with_context(|| format!("failed to write `{}`", path.display()))?;
+    }
+
+    let pkg_json_path = pkg_dir.join("package.json");
+    write(
+        &pkg_json_path,
+        serde_json::to_string_pretty(&plan.package_json)?,
+    )
+    .with_context(|| format!("failed to write `{}`", pkg_json_path.display()))?;
+
+    let mut checksummed = plan
+        .package_json
+        .files
+        .iter()
+        .filter(|f| *f != "bin")
+        .cloned()
+        .collect::<Vec<_>>();
+    checksummed.extend(bin_files);
+    write_checksums(&pkg_dir, &checksummed)?;
+
+    Ok((plan.pkg_dir, statuses))
+}
@@ -0,0 +1,117 @@
+use crate::util::config::NamingConfig;
+use anyhow::{Context, Error};
+use serde::Serialize;
+use swc_node_arch::PlatformDetail;
+
+#[derive(Debug, Serialize)]
+struct Binding {
+    platform: String,
+    arch: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    libc: Option<&'static str>,
+    /// `<package>/<binary>`, which is passed to `require.resolve`.
+    path: String,
+}
+
+/// Generates a javascript file which exports the absolute path of the binary
+/// for the current platform.
+///
+/// Platform packages are selected using the same names and `libc` as
+/// `plugin package`. If `fat` is true, binaries are resolved in the package
+/// for all platforms.
+pub(super) fn generate_loader(
+    main_name: &str,
+    crate_name: &str,
+    platforms: &[PlatformDetail],
+    naming: &NamingConfig,
+    fat: bool,
+) -> Result<String, Error> {
+    let bindings = platforms
+        .iter()
+        .flat_map(|p| {
+            let path = if fat {
+                format!(
+                    "{}/bin/{}/{}",
+                    naming.fat_package_name(main_name, crate_name),
+                    p,
+                    naming.binary_filename(main_name, crate_name, p)
+                )
+            } else {
+                format!(
+                    "{}/{}",
+                    naming.package_name(main_name, crate_name, p),
+                    naming.binary_filename(main_name, crate_name, p)
+                )
+            };
+
+            // An universal binary is used for multiple cpus.
+            p.arch.cpus().into_iter().map(move |arch| Binding {
+                platform: p.platform.to_string(),
+                arch: arch.to_string(),
+                libc: p.libc(),
+                path: path.clone(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let bindings =
+        serde_json::to_string_pretty(&bindings).context("failed to serialize bindings")?;
+    let hint = if fat {
+        format!(
+            "Make sure `{}` is installed.",
+            naming.fat_package_name(main_name, crate_name)
+        )
+    } else {
+        "Make sure optional dependencies are installed.".to_string()
+    };
+    let hint = serde_json::to_string(&format!(". {}", hint))?;
+    let main_name = serde_json::to_string(main_name)?;
+
+    Ok(format!(
+        r#"// Generated by swc-dev. Do not edit.
+"use strict";
+
+const bindings = {bindings};
+
+function currentLibc() {{
+  if (process.platform !== "linux") {{
+    return undefined;
+  }}
+
+  try {{
+    const report = process.report.getReport();
+    return report.header.glibcVersionRuntime ? "glibc" : "musl";
+  }} catch (e) {{
+    return "glibc";
+  }}
+}}
+
+function resolveBinary() {{
+  const libc = currentLibc();
+  const candidates = bindings.filter(
+    (b) =>
+      b.platform === process.platform &&
+      b.arch === process.arch &&
+      (!b.libc || b.libc === libc)
+  );
+
+  for (const binding of candidates) {{
+    try {{
+      return require.resolve(binding.path);
+    }} catch (e) {{}}
+  }}
+
+  throw new Error(
+    `${{{main_name}}}: failed to find the binary for ${{process.platform}}-${{process.arch}}` +
+      (libc ? ` (${{libc}})` : "") +
+      {hint}
+  );
+}}
+
+module.exports = resolveBinary();
+"#,
+        bindings = bindings,
+        main_name = main_name,
+        hint = hint,
+    ))
+}
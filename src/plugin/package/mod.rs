@@ -1,138 +1,624 @@
+use self::{
+    artifacts::{Artifacts, DEFAULT_ARTIFACT_PATTERN},
+    descriptor::{PluginDescriptor, DESCRIPTOR_FILENAME},
+    fat::{create_fat_package, plan_fat_package, FAT_PACKAGE_PLATFORM},
+    loader::generate_loader,
+    notices::{create_notices, NOTICES_FILENAME},
+    plan::PackagePlan,
+    report::{print_matrix, PackageStatus},
+    sbom::create_sbom,
+};
 use crate::{
     plugin::package::package_json::PackageJsonForBin,
     util::{
-        cargo::{get_all_crates, get_cargo_manifest_path, swc_output_dir},
-        node::platform::{all_node_platforms, parse_node_platform},
+        binary::{create_universal_binary, verify_binary},
+        cargo::{
+            cargo_workspace_root, get_cargo_manifest_path, get_resolved_dep_version, resolve_deps,
+            select_plugin_crates, swc_output_dir,
+        },
+        config::{load_config, Config},
+        node::{
+            pack::create_npm_package,
+            platform::{
+                default_node_platforms, parse_node_platform, remove_thin_darwin, rust_target_of,
+                rust_targets_of,
+            },
+            validate::check_package_json,
+        },
     },
 };
 use anyhow::{bail, Context, Error};
+use cargo_metadata::{Version, VersionReq};
 use rayon::prelude::*;
+use serde_json::Value;
+use sha2::{Digest, Sha512};
 use std::{
-    fs::{copy, create_dir_all, read_to_string, write},
+    env::current_dir,
+    fs::{copy, create_dir_all, read, read_dir, read_to_string, remove_file, write},
     path::{Path, PathBuf},
     sync::Arc,
 };
 use structopt::StructOpt;
-use swc_node_arch::PlatformDetail;
-use tracing::{debug, error, info};
+use swc_node_arch::{NodeArch, PlatformDetail};
+use tracing::{debug, error, info, warn};
 
+mod artifacts;
+mod descriptor;
+pub(super) mod fat;
+mod loader;
+mod notices;
 mod package_json;
+mod plan;
+mod report;
+mod sbom;
+
+const SBOM_FILENAME: &str = "sbom.cdx.json";
 
 /// Build plugin packages.
 #[derive(Debug, StructOpt)]
 pub struct PackageCommand {
-    /// Crates to package.
+    /// Crates to package. Defaults to all plugin crates in the workspace.
     #[structopt(long)]
     pub crates: Vec<String>,
 
+    /// Crates to skip.
+    #[structopt(long)]
+    pub exclude: Vec<String>,
+
     /// If specified, the package will contains binaries only for the specified
     /// platforms. If not specified, all platforms will be used.
+    ///
+    /// `darwin-x64` and `darwin-arm64` are skipped if `darwin-universal` is
+    /// specified.
     #[structopt(long)]
     pub platforms: Option<Vec<String>>,
+
+    /// Directory containing binaries built by `plugin build`. Use this if
+    /// `--out-dir` is passed to `plugin build`.
+    ///
+    /// Defaults to `.swc/build` in the cargo target directory.
+    #[structopt(long, parse(from_os_str))]
+    pub build_dir: Option<PathBuf>,
+
+    /// Directory containing binaries with arbitrary names, like artifacts
+    /// collected from CI. It's searched recursively.
+    ///
+    /// Binaries are found by manifests written by `plugin build`, or by
+    /// `--artifact-pattern`.
+    #[structopt(long, parse(from_os_str))]
+    pub artifacts_dir: Option<PathBuf>,
+
+    /// File name of binaries in `--artifacts-dir`. `{crate}`, `{platform}`
+    /// and `{ext}` are replaced.
+    #[structopt(long, default_value = DEFAULT_ARTIFACT_PATTERN)]
+    pub artifact_pattern: String,
+
+    /// Directory to store package files (`*.swc-pkg.tgz`), which is passed to
+    /// `--artifacts-dir` of `plugin publish-deps`.
+    ///
+    /// Defaults to the current directory.
+    #[structopt(long, parse(from_os_str))]
+    pub out_dir: Option<PathBuf>,
+
+    /// Print the packages which would be created, without writing anything.
+    #[structopt(long)]
+    pub dry_run: bool,
+
+    /// Package only platforms which have a built binary, instead of failing.
+    ///
+    /// The command still fails if a binary for a platform in
+    /// `required-platforms` of the configuration is missing.
+    #[structopt(long)]
+    pub partial: bool,
 }
 
 impl PackageCommand {
     pub fn run(self) -> Result<(), Error> {
         let output_base = swc_output_dir()?;
 
-        let crate_names = self.crates.clone();
+        if self.crates.is_empty() {
+            info!("Using all plugin crates in the workspace because `--crates` is not used");
+        }
 
-        let crate_names = if crate_names.is_empty() {
-            info!("Using all crates in the workspace because `--crates` is not used");
-            get_all_crates()?.into_iter().map(|c| c.0).collect()
-        } else {
-            crate_names
-        };
+        let config = load_config()?;
+
+        let crates = select_plugin_crates(&self.crates, &self.exclude)?;
+        let mut main_names = vec![];
+        for (crate_name, manifest_dir) in &crates {
+            let package_json = check_package_json(
+                &manifest_dir.join("package.json"),
+                crate_name,
+                &config.naming,
+            )?;
+            main_names.push(package_json["name"].as_str().unwrap().to_string());
+        }
+        let crate_names = crates.iter().map(|c| c.0.clone()).collect::<Vec<_>>();
 
         let platforms = if let Some(only) = &self.platforms {
-            only.iter()
-                .map(|s| parse_node_platform(s).unwrap())
-                .collect()
+            let mut platforms = only
+                .iter()
+                .map(|s| {
+                    parse_node_platform(s)
+                        .with_context(|| format!("invalid platform `{}` in `--platforms`", s))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            for p in remove_thin_darwin(&mut platforms) {
+                warn!("Skipping `{}` because `darwin-universal` replaces it", p);
+            }
+            platforms
         } else {
-            all_node_platforms()
+            default_node_platforms(config.universal_darwin)
         };
+        let required_platforms = config
+            .required_platforms
+            .iter()
+            .map(|s| {
+                parse_node_platform(s)
+                    .with_context(|| format!("invalid platform `{}` in `required-platforms`", s))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
-        let build_dir = Arc::new(output_base.join("build"));
+        let build_dir = match &self.build_dir {
+            Some(build_dir) => build_dir.clone(),
+            None => output_base.join("build"),
+        };
+        let artifacts = Arc::new(match &self.artifacts_dir {
+            Some(dir) => Artifacts::find(
+                dir,
+                build_dir,
+                &crate_names,
+                &platforms,
+                &self.artifact_pattern,
+            )?,
+            None => Artifacts::in_build_dir(build_dir),
+        });
         let pkgs_dir = Arc::new(output_base.join("pkgs"));
+        let out_dir = match &self.out_dir {
+            Some(out_dir) => out_dir.clone(),
+            None => current_dir().context("failed to get current directory")?,
+        };
+
+        if self.dry_run {
+            return print_plans(
+                &crates,
+                &main_names,
+                &platforms,
+                &pkgs_dir,
+                &out_dir,
+                &artifacts,
+                &config,
+            );
+        }
 
-        let results = platforms
-            .par_iter()
-            .cloned()
-            .flat_map(|platform| {
-                let build_dir = build_dir.clone();
-                let pkgs_dir = pkgs_dir.clone();
-                crate_names.par_iter().map(move |crate_name| {
-                    create_package_for_platform(&pkgs_dir, &build_dir, &crate_name, &platform)
-                        .context("failed to create a package for platform")
+        // Crates which failed to create an universal binary.
+        let mut merge_failed = vec![];
+        if platforms.iter().any(|p| p.arch == NodeArch::Universal) {
+            for crate_name in &crate_names {
+                // Not to package an universal binary of a previous run.
+                let universal_path = universal_binary_path(&artifacts, crate_name)?;
+                if universal_path.exists() {
+                    remove_file(&universal_path).with_context(|| {
+                        format!("failed to remove `{}`", universal_path.display())
+                    })?;
+                }
+
+                if darwin_binaries(&artifacts, crate_name)?
+                    .iter()
+                    .any(|p| !p.is_file())
+                {
+                    debug!(
+                        "Skipping the universal binary for `{}` because a binary for macOS is \
+                         missing",
+                        crate_name
+                    );
+                    continue;
+                }
+
+                if let Err(err) = merge_darwin_binaries(&artifacts, crate_name) {
+                    error!(
+                        "failed to create an universal binary for `{}`: {:?}",
+                        crate_name, err
+                    );
+                    merge_failed.push(crate_name.clone());
+                }
+            }
+        }
+
+        let mut results = if config.fat_package {
+            let per_crate = crate_names
+                .par_iter()
+                .map(|crate_name| {
+                    match create_fat_package(&pkgs_dir, &artifacts, crate_name, &platforms) {
+                        Ok((_, statuses)) => statuses,
+                        Err(err) => {
+                            error!("failed to create a package for all platforms: {:?}", err);
+                            vec![PackageStatus::Failed; platforms.len()]
+                        }
+                    }
                 })
-            })
+                .collect::<Vec<_>>();
+
+            // Platform-major, like the other mode.
+            (0..platforms.len())
+                .flat_map(|i| per_crate.iter().map(move |row| row[i]))
+                .collect::<Vec<_>>()
+        } else {
+            platforms
+                .par_iter()
+                .cloned()
+                .flat_map(|platform| {
+                    let artifacts = artifacts.clone();
+                    let pkgs_dir = pkgs_dir.clone();
+                    crate_names.par_iter().map(move |crate_name| {
+                        let artifact = artifacts.path(crate_name, &platform);
+                        if !artifact.is_file() {
+                            return PackageStatus::Missing;
+                        }
+
+                        match create_package_for_platform(
+                            &pkgs_dir, &artifact, crate_name, &platform,
+                        ) {
+                            Ok(..) => PackageStatus::Packaged,
+                            Err(err) => {
+                                error!("failed to create a package for platform: {:?}", err);
+                                PackageStatus::Failed
+                            }
+                        }
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+        for (i, platform) in platforms.iter().enumerate() {
+            if platform.arch != NodeArch::Universal {
+                continue;
+            }
+            for (j, crate_name) in crate_names.iter().enumerate() {
+                if merge_failed.contains(crate_name) {
+                    results[i * crate_names.len() + j] = PackageStatus::Failed;
+                }
+            }
+        }
+        let statuses = results
+            .chunks(crate_names.len().max(1))
+            .map(|row| row.to_vec())
             .collect::<Vec<_>>();
 
-        let mut error = false;
-        for result in results {
-            match result {
-                Ok(..) => {}
+        print_matrix(&crate_names, &platforms, &statuses);
+
+        let count = |status| results.iter().filter(|&&s| s == status).count();
+        let failed = count(PackageStatus::Failed);
+        let missing = count(PackageStatus::Missing);
+        if failed > 0 {
+            bail!("failed to create {} package(s)", failed);
+        }
+        if missing > 0 {
+            if !self.partial {
+                bail!(
+                    "failed to create packages: {} binary(s) are missing. Use `--partial` to \
+                     package only built binaries",
+                    missing
+                );
+            }
+
+            let missing_required = platforms
+                .iter()
+                .zip(&statuses)
+                .filter(|(p, row)| {
+                    required_platforms.contains(p) && row.contains(&PackageStatus::Missing)
+                })
+                .map(|(p, _)| p.to_string())
+                .collect::<Vec<_>>();
+            if !missing_required.is_empty() {
+                bail!(
+                    "binaries for required platforms are missing: {}",
+                    missing_required.join(", ")
+                );
+            }
+
+            warn!(
+                "Skipped {} package(s) because binaries are missing",
+                missing
+            );
+        }
+
+        // Package files read by `publish-deps`.
+        let packaged = if config.fat_package {
+            crate_names
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| statuses.iter().any(|row| row[j] == PackageStatus::Packaged))
+                .map(|(_, crate_name)| (crate_name, FAT_PACKAGE_PLATFORM.to_string()))
+                .collect::<Vec<_>>()
+        } else {
+            platforms
+                .iter()
+                .zip(&statuses)
+                .flat_map(|(platform, row)| {
+                    crate_names
+                        .iter()
+                        .zip(row)
+                        .filter(|(_, status)| **status == PackageStatus::Packaged)
+                        .map(move |(crate_name, _)| (crate_name, platform.to_string()))
+                })
+                .collect()
+        };
+        if !packaged.is_empty() {
+            create_dir_all(&out_dir)
+                .with_context(|| format!("failed to create `{}`", out_dir.display()))?;
+        }
+        for (crate_name, platform) in packaged {
+            let pkg_dir = pkgs_dir.join(format!("{}-{}", crate_name, platform));
+            let tarball = create_npm_package(&pkg_dir)?;
+
+            let pkg_file = out_dir.join(format!("{}.{}.swc-pkg.tgz", crate_name, platform));
+            copy(&tarball.path, &pkg_file)
+                .with_context(|| format!("failed to copy to `{}`", pkg_file.display()))?;
+            info!("Created {}", pkg_file.display());
+
+            let checksums_file = checksums_path(&out_dir, crate_name, &platform);
+            copy(pkg_dir.join(CHECKSUMS_FILENAME), &checksums_file)
+                .with_context(|| format!("failed to copy to `{}`", checksums_file.display()))?;
+        }
+
+        if let Some(loader) = &config.loader {
+            // The loader is committed, so it should not depend on the platforms
+            // packaged by this run.
+            let mut loader_platforms = default_node_platforms(config.universal_darwin);
+            for p in &platforms {
+                if !loader_platforms.contains(p) {
+                    loader_platforms.push(p.clone());
+                }
+            }
+            remove_thin_darwin(&mut loader_platforms);
+
+            for ((crate_name, manifest_dir), main_name) in crates.iter().zip(main_names.iter()) {
+                let loader_path = manifest_dir.join(loader);
+                let code = generate_loader(
+                    main_name,
+                    crate_name,
+                    &loader_platforms,
+                    &config.naming,
+                    config.fat_package,
+                )?;
+
+                write(&loader_path, &code).with_context(|| {
+                    format!("failed to write the loader to `{}`", loader_path.display())
+                })?;
+                info!("Generated loader at {}", loader_path.display());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Prints what `plugin package` would do, for `--dry-run`.
+///
+/// Fails if any package cannot be planned, like the real run would.
+fn print_plans(
+    crates: &[(String, PathBuf)],
+    main_names: &[String],
+    platforms: &[PlatformDetail],
+    pkgs_dir: &Path,
+    out_dir: &Path,
+    artifacts: &Artifacts,
+    config: &Config,
+) -> Result<(), Error> {
+    let mut missing = 0;
+    let mut failed = 0;
+
+    for (crate_name, _) in crates {
+        if config.fat_package {
+            let plan = match plan_fat_package(pkgs_dir, artifacts, crate_name, platforms) {
+                Ok(v) => v,
+                Err(err) => {
+                    error!(
+                        "failed to plan the package of `{}` for all platforms: {:?}",
+                        crate_name, err
+                    );
+                    failed += 1;
+                    continue;
+                }
+            };
+
+            missing += plan.print(
+                &darwin_binaries(artifacts, crate_name)?,
+                &checksums_path(out_dir, crate_name, FAT_PACKAGE_PLATFORM),
+            );
+            continue;
+        }
+
+        for platform in platforms {
+            let artifact = artifacts.path(crate_name, platform);
+            let plan = match plan_package(pkgs_dir, &artifact, crate_name, platform) {
+                Ok(v) => v,
                 Err(err) => {
-                    error = true;
-                    error!("{:?}", err);
+                    error!(
+                        "failed to plan the package of `{}` for `{}`: {:?}",
+                        crate_name, platform, err
+                    );
+                    failed += 1;
+                    continue;
                 }
+            };
+
+            let sources = if platform.arch == NodeArch::Universal {
+                darwin_binaries(artifacts, crate_name)?.to_vec()
+            } else {
+                vec![]
+            };
+
+            let inputs = if sources.is_empty() {
+                vec![plan.artifact.clone()]
+            } else {
+                sources.clone()
+            };
+            if inputs.iter().any(|p| !p.is_file()) {
+                missing += 1;
             }
+
+            plan.print(
+                &sources,
+                &checksums_path(out_dir, crate_name, &platform.to_string()),
+            );
         }
+    }
 
-        if error {
-            bail!("failed to create packages");
+    if let Some(loader) = &config.loader {
+        for ((_, manifest_dir), main_name) in crates.iter().zip(main_names) {
+            println!(
+                "loader for {}: {}",
+                main_name,
+                manifest_dir.join(loader).display()
+            );
         }
+    }
 
-        Ok(())
+    if missing > 0 {
+        warn!("{} package(s) do not have a built binary", missing);
     }
+    if failed > 0 {
+        bail!("failed to plan {} package(s)", failed);
+    }
+
+    Ok(())
+}
+
+/// Path of the binary built by `plugin build`.
+pub(super) fn artifact_path(
+    build_dir: &Path,
+    crate_name: &str,
+    platform: &PlatformDetail,
+) -> PathBuf {
+    build_dir.join(format!(
+        "{}.{}{}",
+        crate_name,
+        platform,
+        platform.platform.cdylib_ext()
+    ))
+}
+
+/// Paths of binaries for `darwin-x64` and `darwin-arm64`, which are merged into
+/// a binary for `darwin-universal`.
+fn darwin_binaries(artifacts: &Artifacts, crate_name: &str) -> Result<[PathBuf; 2], Error> {
+    let path_for = |platform: &str| -> Result<PathBuf, Error> {
+        Ok(artifacts.path(crate_name, &parse_node_platform(platform)?))
+    };
+
+    Ok([path_for("darwin-x64")?, path_for("darwin-arm64")?])
+}
+
+/// Path of the binary for `darwin-universal` created by
+/// [merge_darwin_binaries].
+fn universal_binary_path(artifacts: &Artifacts, crate_name: &str) -> Result<PathBuf, Error> {
+    Ok(artifact_path(
+        artifacts.build_dir(),
+        crate_name,
+        &parse_node_platform("darwin-universal")?,
+    ))
+}
+
+/// Merges binaries for `darwin-x64` and `darwin-arm64` into a binary for
+/// `darwin-universal`, which is stored in the build directory.
+fn merge_darwin_binaries(artifacts: &Artifacts, crate_name: &str) -> Result<(), Error> {
+    let [x64_path, arm64_path] = darwin_binaries(artifacts, crate_name)?;
+    let x64 =
+        read(&x64_path).with_context(|| format!("failed to read `{}`", x64_path.display()))?;
+    let arm64 =
+        read(&arm64_path).with_context(|| format!("failed to read `{}`", arm64_path.display()))?;
+
+    let universal = create_universal_binary(&[&x64, &arm64])?;
+
+    let build_dir = artifacts.build_dir();
+    create_dir_all(build_dir)
+        .with_context(|| format!("failed to create `{}`", build_dir.display()))?;
+    let universal_path = universal_binary_path(artifacts, crate_name)?;
+    write(&universal_path, &universal)
+        .with_context(|| format!("failed to write `{}`", universal_path.display()))?;
+    debug!(
+        "Created an universal binary at {}",
+        universal_path.display()
+    );
+
+    Ok(())
 }
 
 /// Returns the package directory.
-#[tracing::instrument(name = "build_node_package", skip(pkgs_dir, build_dir))]
+#[tracing::instrument(name = "build_node_package", skip(pkgs_dir, artifact))]
 pub(super) fn create_package_for_platform(
     pkgs_dir: &Path,
-    build_dir: &Path,
+    artifact: &Path,
     crate_name: &str,
     platform: &PlatformDetail,
 ) -> Result<PathBuf, Error> {
     info!("Creating a package for a platform");
 
-    let pkg_dir = pkgs_dir.join(format!("{}-{}", crate_name, platform));
-    let built_bin_path = build_dir.join(format!(
-        "{}.{}{}",
-        crate_name,
-        platform,
-        platform.platform.cdylib_ext()
-    ));
+    let plan = plan_package(pkgs_dir, artifact, crate_name, platform)?;
 
-    create_dir_all(&pkg_dir).with_context(|| {
+    if !plan.artifact.is_file() {
+        bail!(
+            "failed to find built dynamic library from `{}`",
+            plan.artifact.display()
+        )
+    }
+    debug!("Using the dynamic library at `{}`", plan.artifact.display());
+
+    let bin = read(&plan.artifact)
+        .with_context(|| format!("failed to read `{}`", plan.artifact.display()))?;
+    verify_binary(&bin, platform).with_context(|| {
         format!(
-            "failed to create `{}` which is required to create a binary package for `{}`",
-            pkg_dir.display(),
+            "`{}` is not a binary for `{}`",
+            plan.artifact.display(),
             platform
         )
     })?;
 
-    if !built_bin_path.is_file() {
-        bail!(
-            "failed to find built dynamic library from `{}`",
-            built_bin_path.display()
-        )
-    }
-    debug!(
-        "Using the dynamic library at `{}`",
-        built_bin_path.display()
-    );
-    let dylib_filename = format!("lib{}", platform.platform.cdylib_ext());
-    let bin_path = pkg_dir.join(&dylib_filename);
-    copy(&built_bin_path, &bin_path).with_context(|| {
+    create_dir_all(&plan.pkg_dir).with_context(|| {
         format!(
-            "failed to copy built binary file ({}) to package ({})",
-            built_bin_path.display(),
-            bin_path.display(),
+            "failed to create `{}` which is required to create a binary package for `{}`",
+            plan.pkg_dir.display(),
+            platform
         )
     })?;
 
+    for (name, from) in &plan.copied {
+        let to = plan.pkg_dir.join(name);
+        copy(from, &to).with_context(|| {
+            format!(
+                "failed to copy `{}` to package ({})",
+                from.display(),
+                to.display(),
+            )
+        })?;
+    }
+
+    for (name, content) in &plan.generated {
+        let path = plan.pkg_dir.join(name);
+        write(&path, content).with_context(|| format!("failed to write `{}`", path.display()))?;
+    }
+
+    let mut descriptor = plan.descriptor.clone();
+    descriptor.binary_sha512 = Some(hex::encode(Sha512::digest(&bin)));
+    let descriptor_path = plan.pkg_dir.join(DESCRIPTOR_FILENAME);
+    write(&descriptor_path, serde_json::to_string_pretty(&descriptor)?)
+        .with_context(|| format!("failed to write `{}`", descriptor_path.display()))?;
+
+    write_checksums(&plan.pkg_dir, &plan.package_json.files)?;
+
+    Ok(plan.pkg_dir)
+}
+
+/// Computes the contents of the package for `platform` without writing
+/// anything.
+fn plan_package(
+    pkgs_dir: &Path,
+    artifact: &Path,
+    crate_name: &str,
+    platform: &PlatformDetail,
+) -> Result<PackagePlan, Error> {
+    let pkg_dir = pkgs_dir.join(format!("{}-{}", crate_name, platform));
+    let artifact = artifact.to_path_buf();
+
     let manifest_path = get_cargo_manifest_path(crate_name.to_string())
         .context("failed to get the path of cargo manifest")?;
     let manifest_dir = manifest_path.parent().unwrap();
@@ -147,8 +633,12 @@ pub(super) fn create_package_for_platform(
     }
 
     let package_json_str = read_to_string(&package_json_path)?;
+    let main_package_json: Value = serde_json::from_str(&package_json_str)
+        .with_context(|| format!("failed to parse `{}`", package_json_path.display()))?;
+
+    let config = load_config()?;
 
-    let mut bin_pkg_json: PackageJsonForBin = serde_json::from_str(&package_json_str)
+    let mut bin_pkg_json = PackageJsonForBin::from_main(&package_json_str, &config.package_json)
         .with_context(|| {
             format!(
                 "failed to create the package.json file for platform package from the main \
@@ -158,31 +648,303 @@ pub(super) fn create_package_for_platform(
         })?;
     let main_name = bin_pkg_json.name.clone();
 
-    bin_pkg_json.name = format!("{}-{}", bin_pkg_json.name, platform);
+    let mut copied = vec![];
+    let mut generated = vec![];
+
+    let dylib_filename = config
+        .naming
+        .binary_filename(&main_name, crate_name, platform);
+    copied.push((dylib_filename.clone(), artifact.clone()));
+
+    bin_pkg_json.name = config.naming.package_name(&main_name, crate_name, platform);
     bin_pkg_json.description = format!(
         "This package is part of {}. This package will be installed only for `{}`.",
         main_name, platform
     );
 
     bin_pkg_json.os.push(platform.platform);
-    bin_pkg_json.cpu.push(platform.arch);
+    bin_pkg_json.cpu.extend(platform.arch.cpus());
+    bin_pkg_json.libc.extend(platform.libc().map(String::from));
 
     bin_pkg_json.files.push(dylib_filename.clone());
     bin_pkg_json.main = dylib_filename;
 
-    // let package_json = PackageJsonForBin {
-    //     name: crate_name.to_string(),
-    // };
+    let license_files = find_license_files(manifest_dir)?;
+    if license_files.is_empty() {
+        warn!(
+            "failed to find a license file for `{}`. Add `LICENSE` to the crate directory or the \
+             workspace root",
+            crate_name
+        );
+    }
+    for path in license_files {
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        bin_pkg_json.files.push(name.clone());
+        copied.push((name, path));
+    }
 
-    let bin_json_path = pkg_dir.join("package.json");
-    let bin_pkg_json = serde_json::to_string_pretty(&bin_pkg_json)
-        .context("failed to serialize package.json file for the binary package")?;
-    write(&bin_json_path, &bin_pkg_json).with_context(|| {
+    generated.push((
+        "README.md".to_string(),
+        platform_readme(&bin_pkg_json.name, &main_name, platform),
+    ));
+    bin_pkg_json.files.push("README.md".into());
+
+    if let Some(range) = swc_core_peer_range(crate_name, &config)? {
+        bin_pkg_json
+            .rest
+            .entry("peerDependencies")
+            .or_insert_with(|| Value::Object(Default::default()))
+            .as_object_mut()
+            .context("`peerDependencies` should be an object")?
+            .insert("@swc/core".to_string(), Value::String(range));
+    }
+
+    let target = rust_target_of(platform);
+    let deps = resolve_deps(crate_name.to_string(), target.clone())?;
+    let sbom = create_sbom(&deps, target.as_deref());
+    generated.push((SBOM_FILENAME.into(), serde_json::to_string_pretty(&sbom)?));
+    if config.include_sbom {
+        bin_pkg_json.files.push(SBOM_FILENAME.into());
+    }
+
+    let notices = create_notices(&deps, config.allowed_licenses.as_deref()).with_context(|| {
         format!(
-            "failed to write package.json file to `{}`",
-            bin_json_path.display()
+            "failed to collect licenses of crates linked into `{}`",
+            crate_name
         )
     })?;
+    generated.push((NOTICES_FILENAME.into(), notices));
+    bin_pkg_json.files.push(NOTICES_FILENAME.into());
+
+    let descriptor = plugin_descriptor(
+        crate_name,
+        &main_name,
+        &deps.root.version.to_string(),
+        platform,
+        &bin_pkg_json.main,
+    )?;
+    bin_pkg_json.files.push(DESCRIPTOR_FILENAME.into());
+
+    let bin_pkg_json_str = serde_json::to_string_pretty(&bin_pkg_json)
+        .context("failed to serialize package.json file for the binary package")?;
+    generated.push(("package.json".into(), bin_pkg_json_str));
+
+    Ok(PackagePlan {
+        pkg_dir,
+        artifact,
+        main_package_json,
+        package_json: bin_pkg_json,
+        descriptor,
+        copied,
+        generated,
+    })
+}
+
+/// Creates `swc-plugin.json` for the binary of `crate_name`, without the hash.
+fn plugin_descriptor(
+    crate_name: &str,
+    main_name: &str,
+    version: &str,
+    platform: &PlatformDetail,
+    binary: &str,
+) -> Result<PluginDescriptor, Error> {
+    let dep_version = |dep_name: &str| {
+        get_resolved_dep_version(crate_name.to_string(), dep_name.to_string())
+            .with_context(|| format!("failed to get the version of `{}`", dep_name))
+    };
 
-    Ok(pkg_dir)
+    let mut descriptor = PluginDescriptor::new(
+        main_name.to_string(),
+        crate_name.to_string(),
+        version.to_string(),
+        rust_targets_of(platform),
+        platform.clone(),
+        binary.to_string(),
+    );
+    descriptor.swc_plugin = dep_version("swc_plugin")?;
+    descriptor.swc_common = dep_version("swc_common")?;
+    descriptor.abi_version = dep_version("abi_stable")?;
+
+    Ok(descriptor)
+}
+
+pub(super) const CHECKSUMS_FILENAME: &str = "checksums.txt";
+
+/// Writes `checksums.txt`, which contains sha512 of `package.json` and `files`
+/// in the format of `sha512sum`.
+fn write_checksums(pkg_dir: &Path, files: &[String]) -> Result<(), Error> {
+    let mut names = files.to_vec();
+    names.push("package.json".into());
+    names.sort();
+    names.dedup();
+
+    let mut buf = String::new();
+    for name in &names {
+        let path = pkg_dir.join(name);
+        let bytes = read(&path).with_context(|| format!("failed to read `{}`", path.display()))?;
+        buf.push_str(&format!(
+            "{}  {}\n",
+            hex::encode(Sha512::digest(&bytes)),
+            name
+        ));
+    }
+
+    let path = pkg_dir.join(CHECKSUMS_FILENAME);
+    write(&path, &buf).with_context(|| format!("failed to write `{}`", path.display()))?;
+
+    Ok(())
+}
+
+/// Path of `checksums.txt` copied next to the package file, like
+/// `my_plugin.linux-x64-gnu.checksums.txt`.
+///
+/// It's not packed. Files of a downloaded package can be verified with it
+/// using `sha512sum -c`.
+pub(super) fn checksums_path(out_dir: &Path, crate_name: &str, platform: &str) -> PathBuf {
+    out_dir.join(format!(
+        "{}.{}.{}",
+        crate_name, platform, CHECKSUMS_FILENAME
+    ))
+}
+
+/// Version range of `@swc/core` which is compatible with the version of
+/// `swc_plugin` used by the crate.
+fn swc_core_peer_range(crate_name: &str, config: &Config) -> Result<Option<String>, Error> {
+    if config.swc_core_peer.is_empty() {
+        return Ok(None);
+    }
+
+    let version = match get_resolved_dep_version(crate_name.to_string(), "swc_plugin".into())? {
+        Some(v) => v,
+        None => {
+            warn!("`{}` does not depend on `swc_plugin`", crate_name);
+            return Ok(None);
+        }
+    };
+    let version = Version::parse(&version)?;
+
+    for peer in &config.swc_core_peer {
+        let req = VersionReq::parse(&peer.swc_plugin).with_context(|| {
+            format!(
+                "`{}` in `swc-core-peer` is not a valid version requirement",
+                peer.swc_plugin
+            )
+        })?;
+
+        if req.matches(&version) {
+            return Ok(Some(peer.swc_core.clone()));
+        }
+    }
+
+    warn!(
+        "`swc-core-peer` does not have an entry for `swc_plugin@{}`",
+        version
+    );
+
+    Ok(None)
+}
+
+fn platform_readme(name: &str, main_name: &str, platform: &PlatformDetail) -> String {
+    let targets = rust_targets_of(platform);
+    let target = if targets.is_empty() {
+        "unknown".into()
+    } else {
+        targets.join(", ")
+    };
+
+    [
+        format!("# `{}`", name),
+        String::new(),
+        format!(
+            "This is the `{}` binary of [`{}`](https://www.npmjs.com/package/{}).",
+            platform, main_name, main_name
+        ),
+        format!(
+            "It's installed by `{}` as an optional dependency, so you don't need to install it \
+             directly.",
+            main_name
+        ),
+        String::new(),
+        format!("- Platform: `{}`", platform),
+        format!("- Rust target: `{}`", target),
+        String::new(),
+    ]
+    .join("\n")
+}
+
+/// Finds license files of the crate.
+///
+/// If the crate directory does not have license files, license files in the
+/// workspace root are used.
+fn find_license_files(manifest_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let ws_root = cargo_workspace_root()?;
+
+    for dir in [manifest_dir, &*ws_root].iter() {
+        let files = license_files_in(dir)?;
+        if !files.is_empty() {
+            return Ok(files);
+        }
+    }
+
+    Ok(vec![])
+}
+
+/// Returns license files in `dir`, sorted by name.
+fn license_files_in(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut names = vec![];
+
+    for e in read_dir(dir).with_context(|| format!("failed to read `{}`", dir.display()))? {
+        let e = e?;
+        if !e.file_type()?.is_file() {
+            continue;
+        }
+
+        let name = e.file_name().to_string_lossy().to_string();
+        let upper = name.to_ascii_uppercase();
+        if upper.starts_with("LICENSE")
+            || upper.starts_with("LICENCE")
+            || upper.starts_with("COPYING")
+        {
+            names.push(name);
+        }
+    }
+
+    names.sort();
+    Ok(names.into_iter().map(|name| dir.join(name)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn darwin_binaries_for_default_platforms() {
+        let artifacts = Artifacts::in_build_dir(PathBuf::from("build"));
+
+        for platform in default_node_platforms(true) {
+            if platform.arch != NodeArch::Universal {
+                continue;
+            }
+
+            let paths = darwin_binaries(&artifacts, "plugin").unwrap();
+            assert_eq!(
+                paths,
+                [
+                    PathBuf::from("build/plugin.darwin-x64.dylib"),
+                    PathBuf::from("build/plugin.darwin-arm64.dylib"),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn universal_binary_is_packaged_from_build_dir() {
+        let artifacts = Artifacts::in_build_dir(PathBuf::from("build"));
+        let universal = parse_node_platform("darwin-universal").unwrap();
+
+        assert_eq!(
+            universal_binary_path(&artifacts, "plugin").unwrap(),
+            artifacts.path("plugin", &universal)
+        );
+    }
 }
@@ -0,0 +1,240 @@
+use super::license_files_in;
+use crate::util::cargo::ResolvedDeps;
+use anyhow::{bail, Context, Error};
+use cargo_metadata::Package;
+use std::{fs::read_to_string, path::PathBuf};
+use tracing::error;
+
+pub(super) const NOTICES_FILENAME: &str = "THIRD_PARTY_NOTICES";
+
+/// Creates `THIRD_PARTY_NOTICES` from license metadata and license files of
+/// crates linked into the binary.
+///
+/// If `allowed` is specified, the license of each crate should be satisfiable
+/// with licenses in it.
+pub(super) fn create_notices(
+    deps: &ResolvedDeps,
+    allowed: Option<&[String]>,
+) -> Result<String, Error> {
+    let mut problems = 0;
+    let mut buf = format!(
+        "THIRD-PARTY SOFTWARE NOTICES\n\n`{}` includes the following third-party crates.\n",
+        deps.root.name
+    );
+
+    for p in &deps.packages {
+        let license_files = license_files_of(p)?;
+
+        match (&p.license, allowed) {
+            (None, _) if license_files.is_empty() => {
+                error!("`{}@{}` does not have a license", p.name, p.version);
+                problems += 1;
+            }
+            (None, Some(_)) => {
+                error!(
+                    "`{}@{}` specifies only `license-file`, so its license cannot be checked with \
+                     `allowed-licenses`",
+                    p.name, p.version
+                );
+                problems += 1;
+            }
+            (Some(license), Some(allowed)) if !is_allowed(license, allowed) => {
+                error!(
+                    "the license of `{}@{}` (`{}`) is not allowed by `allowed-licenses`",
+                    p.name, p.version, license
+                );
+                problems += 1;
+            }
+            _ => {}
+        }
+
+        buf.push_str(&format!("\n{}\n{} {}\n", "=".repeat(80), p.name, p.version));
+        if let Some(license) = &p.license {
+            buf.push_str(&format!("License: {}\n", license));
+        }
+        if let Some(repo) = &p.repository {
+            buf.push_str(&format!("Repository: {}\n", repo));
+        }
+
+        for path in license_files {
+            let text = read_to_string(&path)
+                .with_context(|| format!("failed to read `{}`", path.display()))?;
+            let name = path.file_name().unwrap().to_string_lossy();
+            buf.push_str(&format!("\n--- {} ---\n\n{}\n", name, text.trim_end()));
+        }
+    }
+
+    if problems > 0 {
+        bail!(
+            "{} crate(s) linked into `{}` have license problems; see the log above",
+            problems,
+            deps.root.name
+        )
+    }
+
+    Ok(buf)
+}
+
+fn license_files_of(p: &Package) -> Result<Vec<PathBuf>, Error> {
+    if let Some(path) = p.license_file() {
+        return Ok(vec![path.into()]);
+    }
+
+    match p.manifest_path.parent() {
+        Some(dir) => license_files_in(dir.as_std_path()),
+        None => Ok(vec![]),
+    }
+}
+
+/// Returns true if the SPDX expression `license` is satisfied by licenses in
+/// `allowed`.
+///
+/// `/`, which is used by old crates, is treated as `OR`.
+fn is_allowed(license: &str, allowed: &[String]) -> bool {
+    let tokens = license
+        .replace('/', " OR ")
+        .replace('(', " ( ")
+        .replace(')', " ) ")
+        .split_whitespace()
+        .map(String::from)
+        .collect::<Vec<_>>();
+
+    let mut parser = Parser {
+        tokens: &tokens,
+        pos: 0,
+        allowed,
+    };
+    match parser.parse_or() {
+        Some(v) if parser.pos == tokens.len() => v,
+        _ => false,
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [String],
+    pos: usize,
+    allowed: &'a [String],
+}
+
+impl Parser<'_> {
+    fn next_is(&self, s: &str) -> bool {
+        self.tokens.get(self.pos).map_or(false, |t| t == s)
+    }
+
+    fn parse_or(&mut self) -> Option<bool> {
+        let mut v = self.parse_and()?;
+        while self.next_is("OR") {
+            self.pos += 1;
+            // Don't short-circuit, to validate the whole expression.
+            v |= self.parse_and()?;
+        }
+        Some(v)
+    }
+
+    fn parse_and(&mut self) -> Option<bool> {
+        let mut v = self.parse_license()?;
+        while self.next_is("AND") {
+            self.pos += 1;
+            v &= self.parse_license()?;
+        }
+        Some(v)
+    }
+
+    fn parse_license(&mut self) -> Option<bool> {
+        if self.next_is("(") {
+            self.pos += 1;
+            let v = self.parse_or()?;
+            if !self.next_is(")") {
+                return None;
+            }
+            self.pos += 1;
+            return Some(v);
+        }
+
+        let id = self.parse_id()?;
+
+        if self.next_is("WITH") {
+            self.pos += 1;
+            let exception = self.parse_id()?;
+
+            let with = format!("{} WITH {}", id, exception);
+            return Some(self.allowed.contains(&with) || self.allowed.contains(&id));
+        }
+
+        Some(self.allowed.contains(&id))
+    }
+
+    fn parse_id(&mut self) -> Option<String> {
+        let id = self.tokens.get(self.pos)?;
+        if ["(", ")", "AND", "OR", "WITH"].contains(&&**id) {
+            return None;
+        }
+        self.pos += 1;
+        Some(id.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed(license: &str, allowed: &[&str]) -> bool {
+        let allowed = allowed.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        is_allowed(license, &allowed)
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let license = "MIT OR Apache-2.0 AND GPL-3.0";
+        assert!(allowed(license, &["MIT"]));
+        assert!(!allowed(license, &["Apache-2.0"]));
+        assert!(allowed(license, &["Apache-2.0", "GPL-3.0"]));
+    }
+
+    #[test]
+    fn parentheses() {
+        let license = "(MIT OR Apache-2.0) AND GPL-3.0";
+        assert!(!allowed(license, &["MIT"]));
+        assert!(allowed(license, &["MIT", "GPL-3.0"]));
+        assert!(allowed("((MIT))", &["MIT"]));
+    }
+
+    #[test]
+    fn with_exception() {
+        let license = "Apache-2.0 WITH LLVM-exception";
+        assert!(allowed(license, &["Apache-2.0"]));
+        assert!(allowed(license, &["Apache-2.0 WITH LLVM-exception"]));
+        assert!(!allowed(license, &["MIT", "LLVM-exception"]));
+        assert!(allowed(
+            "GPL-2.0 WITH Classpath-exception-2.0 OR MIT",
+            &["MIT"]
+        ));
+    }
+
+    #[test]
+    fn legacy_slash() {
+        assert!(allowed("MIT/Apache-2.0", &["Apache-2.0"]));
+        assert!(allowed("MIT / Apache-2.0", &["MIT"]));
+        assert!(!allowed("MIT/Apache-2.0", &["GPL-3.0"]));
+    }
+
+    #[test]
+    fn malformed() {
+        for license in &[
+            "",
+            "MIT OR",
+            "OR MIT",
+            "AND MIT",
+            "(MIT",
+            "MIT)",
+            "()",
+            "MIT Apache-2.0",
+            "MIT WITH",
+            "MIT WITH )",
+            "MIT WITH OR Apache-2.0",
+            "WITH MIT",
+        ] {
+            assert!(!allowed(license, &["MIT", "Apache-2.0"]), "{:?}", license);
+        }
+    }
+}
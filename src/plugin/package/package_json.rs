@@ -1,11 +1,47 @@
-use crate::util::AHashMap;
+use crate::util::config::PackageJsonConfig;
+use anyhow::{Context, Error};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use swc_node_arch::{NodeArch, NodePlatform};
 
+/// Fields of the main `package.json` which are not copied to binary packages
+/// by default.
+///
+/// This can be changed with `package-json.keep` and `package-json.drop` of the
+/// configuration.
+///
+/// `exports` is dropped because the loader resolves the binary by its path,
+/// which would be hidden by `exports` of the main package.
+pub const DEFAULT_DENYLIST: &[&str] = &[
+    "bin",
+    "bundleDependencies",
+    "bundledDependencies",
+    "dependencies",
+    "devDependencies",
+    "exports",
+    "optionalDependencies",
+    "peerDependencies",
+    "peerDependenciesMeta",
+    "scripts",
+    "workspaces",
+];
+
+/// Fields which are always overriden for binary packages.
+const OVERRIDDEN: &[&str] = &[
+    "name",
+    "version",
+    "os",
+    "cpu",
+    "libc",
+    "files",
+    "description",
+    "main",
+];
+
 /// A `package.json` file for a binary package.
 ///
 /// (binary package means a platfomr-dependant package)
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PackageJsonForBin {
     pub name: String,
@@ -17,35 +53,83 @@ pub struct PackageJsonForBin {
     #[serde(skip_deserializing, skip_serializing_if = "Vec::is_empty")]
     pub cpu: Vec<NodeArch>,
 
+    #[serde(skip_deserializing, skip_serializing_if = "Vec::is_empty")]
+    pub libc: Vec<String>,
+
     #[serde(skip_deserializing, skip_serializing_if = "Vec::is_empty")]
     pub files: Vec<String>,
 
     #[serde(skip_deserializing, skip_serializing_if = "String::is_empty")]
     pub description: String,
 
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub keywords: Vec<String>,
+    #[serde(skip_deserializing, skip_serializing_if = "String::is_empty")]
+    pub main: String,
 
-    #[serde(default, skip_serializing_if = "String::is_empty")]
-    pub author: String,
+    /// Other fields copied from the main `package.json`, like `engines`.
+    #[serde(flatten)]
+    pub rest: Map<String, Value>,
+}
 
-    #[serde(default, skip_serializing_if = "String::is_empty")]
-    pub homepage: String,
+impl PackageJsonForBin {
+    /// Creates a `package.json` for a binary package from the main
+    /// `package.json`, keeping fields allowed by `config`.
+    pub fn from_main(main_package_json: &str, config: &PackageJsonConfig) -> Result<Self, Error> {
+        let mut pkg: PackageJsonForBin =
+            serde_json::from_str(main_package_json).context("failed to parse package.json")?;
 
-    pub license: String,
+        pkg.rest
+            .retain(|key, _| !OVERRIDDEN.contains(&&**key) && is_copied(config, key));
 
-    #[serde(default, skip_serializing_if = "String::is_empty")]
-    pub main: String,
+        Ok(pkg)
+    }
+}
+
+fn is_copied(config: &PackageJsonConfig, key: &str) -> bool {
+    if config.drop.iter().any(|v| v == key) {
+        return false;
+    }
+
+    if config.keep.iter().any(|v| v == key) {
+        return true;
+    }
+
+    !DEFAULT_DENYLIST.contains(&key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAIN: &str = r#"{
+        "name": "@example/plugin",
+        "version": "1.0.0",
+        "main": "index.js",
+        "exports": { ".": "./index.js" },
+        "engines": { "node": ">=12" },
+        "scripts": { "build": "swc-dev plugin build" },
+        "peerDependencies": { "@swc/core": "^1.2.100" }
+    }"#;
 
-    #[serde(skip_deserializing, skip_serializing_if = "AHashMap::is_empty")]
-    pub engines: AHashMap<String, String>,
+    #[test]
+    fn copied_fields() {
+        let pkg = PackageJsonForBin::from_main(MAIN, &Default::default()).unwrap();
 
-    #[serde(default, skip_serializing_if = "AHashMap::is_empty")]
-    pub publish_config: AHashMap<String, String>,
+        assert_eq!(pkg.name, "@example/plugin");
+        assert_eq!(pkg.main, "");
+        assert_eq!(pkg.rest.keys().collect::<Vec<_>>(), vec!["engines"]);
+    }
 
-    #[serde(default, skip_serializing_if = "AHashMap::is_empty")]
-    pub repository: AHashMap<String, String>,
+    #[test]
+    fn keep_and_drop() {
+        let config = PackageJsonConfig {
+            keep: vec!["exports".into(), "peerDependencies".into()],
+            drop: vec!["engines".into()],
+        };
+        let pkg = PackageJsonForBin::from_main(MAIN, &config).unwrap();
 
-    #[serde(default, skip_serializing_if = "AHashMap::is_empty")]
-    pub bugs: AHashMap<String, String>,
+        assert_eq!(
+            pkg.rest.keys().collect::<Vec<_>>(),
+            vec!["exports", "peerDependencies"]
+        );
+    }
 }
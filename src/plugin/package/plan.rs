@@ -0,0 +1,72 @@
+use super::{
+    descriptor::{PluginDescriptor, DESCRIPTOR_FILENAME},
+    package_json::PackageJsonForBin,
+};
+use crate::util::node::{json_diff, pack::packed_files};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Contents of a platform package, computed by
+/// [plan_package](super::plan_package).
+#[derive(Debug)]
+pub(super) struct PackagePlan {
+    pub pkg_dir: PathBuf,
+    /// The binary built by `plugin build`.
+    pub artifact: PathBuf,
+    /// The main `package.json`.
+    pub main_package_json: Value,
+    pub package_json: PackageJsonForBin,
+    /// `swc-plugin.json`, without the hash of the binary.
+    pub descriptor: PluginDescriptor,
+    /// Files copied to the package directory, with their source.
+    pub copied: Vec<(String, PathBuf)>,
+    /// Files generated in the package directory, with their content.
+    pub generated: Vec<(String, String)>,
+}
+
+impl PackagePlan {
+    /// Prints the plan for `package --dry-run`.
+    ///
+    /// `sources` are binaries used to create the artifact, if the artifact is
+    /// created by `plugin package` itself. `checksums` is where
+    /// `checksums.txt` is copied to.
+    pub fn print(&self, sources: &[PathBuf], checksums: &Path) {
+        println!("{}", self.pkg_dir.display());
+
+        println!(
+            "  artifact: {}",
+            describe_file(&self.artifact, sources.is_empty())
+        );
+        for source in sources {
+            println!("    merged from: {}", describe_file(source, true));
+        }
+
+        println!("  package.json:");
+        let generated = serde_json::to_value(&self.package_json).unwrap_or(Value::Null);
+        for line in json_diff(&self.main_package_json, &generated) {
+            println!("    {}", line);
+        }
+
+        println!("  files:");
+        let mut names = self
+            .copied
+            .iter()
+            .map(|(name, _)| name.clone())
+            .chain(self.generated.iter().map(|(name, _)| name.clone()))
+            .collect::<Vec<_>>();
+        names.push(DESCRIPTOR_FILENAME.into());
+        for name in packed_files(Some(&self.package_json.files), names) {
+            println!("    {}", name);
+        }
+        println!("  checksums: {}", checksums.display());
+        println!();
+    }
+}
+
+pub(super) fn describe_file(path: &Path, check: bool) -> String {
+    if check && !path.is_file() {
+        format!("{} (MISSING)", path.display())
+    } else {
+        path.display().to_string()
+    }
+}
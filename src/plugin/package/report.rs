@@ -0,0 +1,54 @@
+use swc_node_arch::PlatformDetail;
+
+/// Result of packaging a crate for a platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum PackageStatus {
+    Packaged,
+    /// The binary is not built.
+    Missing,
+    Failed,
+}
+
+impl PackageStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            PackageStatus::Packaged => "packaged",
+            PackageStatus::Missing => "missing",
+            PackageStatus::Failed => "FAILED",
+        }
+    }
+}
+
+/// Prints the status of each package as a table. Rows are platforms and
+/// columns are crates.
+///
+/// `statuses[i][j]` is the status of `crate_names[j]` for `platforms[i]`.
+pub(super) fn print_matrix(
+    crate_names: &[String],
+    platforms: &[PlatformDetail],
+    statuses: &[Vec<PackageStatus>],
+) {
+    let first_width = platforms
+        .iter()
+        .map(|p| p.platform_arch_abi.len())
+        .max()
+        .unwrap_or(0);
+    let widths = crate_names
+        .iter()
+        .map(|name| name.len().max(PackageStatus::Packaged.as_str().len()))
+        .collect::<Vec<_>>();
+
+    let mut header = format!("{:1$}", "", first_width);
+    for (name, width) in crate_names.iter().zip(&widths) {
+        header.push_str(&format!("  {:1$}", name, width));
+    }
+    println!("{}", header.trim_end());
+
+    for (platform, row) in platforms.iter().zip(statuses) {
+        let mut line = format!("{:1$}", platform.platform_arch_abi, first_width);
+        for (status, width) in row.iter().zip(&widths) {
+            line.push_str(&format!("  {:1$}", status.as_str(), width));
+        }
+        println!("{}", line.trim_end());
+    }
+}
@@ -0,0 +1,82 @@
+use crate::util::cargo::ResolvedDeps;
+use cargo_metadata::Package;
+use serde_json::{json, Value};
+
+/// Creates a CycloneDX SBOM (json, spec version 1.3) from the resolved
+/// dependency graph.
+///
+/// The output does not contain timestamps or random serial numbers, so it's
+/// reproducible.
+pub(super) fn create_sbom(deps: &ResolvedDeps, target: Option<&str>) -> Value {
+    let mut metadata = json!({
+        "tools": [{
+            "vendor": "swc",
+            "name": "swc-dev",
+            "version": env!("CARGO_PKG_VERSION"),
+        }],
+        "component": component(&deps.root),
+    });
+    if let Some(target) = target {
+        metadata["properties"] = json!([{
+            "name": "cdx:rustc:target",
+            "value": target,
+        }]);
+    }
+
+    let dependencies = deps
+        .edges
+        .iter()
+        .map(|(id, deps)| {
+            json!({
+                "ref": id,
+                "dependsOn": deps,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.3",
+        "version": 1,
+        "metadata": metadata,
+        "components": deps.packages.iter().map(component).collect::<Vec<_>>(),
+        "dependencies": dependencies,
+    })
+}
+
+fn component(p: &Package) -> Value {
+    let mut c = json!({
+        "type": "library",
+        "bom-ref": p.id.repr,
+        "name": p.name,
+        "version": p.version.to_string(),
+        "purl": format!("pkg:cargo/{}@{}", p.name, p.version),
+    });
+
+    if let Some(desc) = &p.description {
+        c["description"] = json!(desc);
+    }
+
+    if let Some(license) = &p.license {
+        c["licenses"] = json!([{ "expression": license }]);
+    }
+
+    let mut refs = vec![];
+    if let Some(source) = &p.source {
+        refs.push(json!({
+            "type": "distribution",
+            "url": source.repr,
+        }));
+    }
+    if let Some(repo) = &p.repository {
+        refs.push(json!({
+            "type": "vcs",
+            "url": repo,
+        }));
+    }
+    if !refs.is_empty() {
+        c["externalReferences"] = Value::Array(refs);
+    }
+
+    c
+}
@@ -2,10 +2,22 @@ use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
 pub struct BasePublishCommand {
-    /// Defaults whole crates.
+    /// Defaults to all plugin crates in the workspace.
     #[structopt(long)]
     pub crates: Vec<String>,
 
+    /// Crates to skip.
+    #[structopt(long)]
+    pub exclude: Vec<String>,
+
     #[structopt(long)]
     pub access: Option<String>,
+
+    /// Dist-tag of the packages. The main package and platform packages
+    /// always use the same tag.
+    ///
+    /// Defaults to `canary` / `next` for prerelease versions and `latest` for
+    /// others.
+    #[structopt(long)]
+    pub tag: Option<String>,
 }
@@ -1,17 +1,41 @@
-use self::base::BasePublishCommand;
-use crate::util::{cargo::get_all_crates, node::publish_tarball_to_npm, AHashMap};
+use self::{
+    base::BasePublishCommand,
+    state::{Action, PublishState, Step},
+    tag::TagCommand,
+};
+use crate::{
+    plugin::package::fat::FAT_PACKAGE_PLATFORM,
+    util::{
+        cargo::select_plugin_crates,
+        config::load_config,
+        node::{
+            json_diff,
+            pack::{integrity_of, read_package_json_in_tarball},
+            platform::parse_node_platform,
+            publish_tarball_to_npm,
+            registry::{default_dist_tag, validate_dist_tag, NpmRegistry, Npmrc},
+            validate::check_package_json,
+        },
+        AHashMap,
+    },
+};
 use anyhow::{bail, Context, Result};
 use serde_json::Value;
 use std::{
     env::current_dir,
-    fs::{self, read_dir, read_to_string},
+    fs::{self, read_dir},
     path::{Path, PathBuf},
-    str::FromStr,
 };
 use structopt::StructOpt;
-use tracing::info;
+use tracing::{info, warn};
 
 mod base;
+mod state;
+mod tag;
+
+const UNIVERSAL_DARWIN: &str = "darwin-universal";
+/// Platforms replaced by [UNIVERSAL_DARWIN].
+const THIN_DARWIN: &[&str] = &["darwin-x64", "darwin-arm64"];
 
 /// Publish package for each platforms and patch package.json to include
 /// `optionalDependencies`
@@ -19,114 +43,405 @@ mod base;
 pub struct PublishDepsCommand {
     #[structopt(flatten)]
     pub base: BasePublishCommand,
+    /// Directory containing package files (`*.swc-pkg.tgz`). Use the
+    /// `--out-dir` of `plugin build` if it's specified.
+    ///
     /// Defaults to current working directory.
     #[structopt(long, default_value)]
     pub artifacts_dir: String,
+
+    /// Print the changes to `package.json` and the packages which would be
+    /// published, without writing files or publishing.
+    #[structopt(long)]
+    pub dry_run: bool,
+
+    /// Continue the previous run from the first step which is not done.
+    ///
+    /// Other options are ignored because the plan is read from
+    /// `.swc/publish-state.json`.
+    #[structopt(long)]
+    pub resume: bool,
+
+    /// Publish using `npm publish` instead of the built-in registry client.
+    #[structopt(long)]
+    pub npm: bool,
+
+    #[structopt(subcommand)]
+    pub cmd: Option<PublishDepsSubcommand>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum PublishDepsSubcommand {
+    Tag(TagCommand),
 }
 
 impl PublishDepsCommand {
     pub fn run(self) -> Result<()> {
+        if let Some(PublishDepsSubcommand::Tag(cmd)) = self.cmd {
+            return cmd.run();
+        }
+
+        if let Some(tag) = &self.base.tag {
+            validate_dist_tag(tag)?;
+        }
+
+        if self.resume {
+            let state = PublishState::load()?.with_context(|| {
+                "there's no publish to resume. Run `publish-deps` without `--resume`"
+            })?;
+            return execute(state);
+        }
+
+        if !self.dry_run {
+            if let Some(state) = PublishState::load()? {
+                if !state.is_done() {
+                    bail!(
+                        "the previous publish did not finish. Use `--resume` to continue it, or \
+                         remove `{}` to start over",
+                        PublishState::path()?.display()
+                    )
+                }
+            }
+        }
+
         let artifacts_dir = if self.artifacts_dir.is_empty() {
             current_dir().context("failed to get current directory")?
         } else {
             PathBuf::from(self.artifacts_dir)
         };
 
-        let enabled_crates = self.base.crates;
         let access = self.base.access;
 
-        let crates = get_all_crates()?
-            .into_iter()
-            .filter(|(name, _)| enabled_crates.is_empty() || enabled_crates.contains(&name))
-            .collect::<AHashMap<_, _>>();
+        let crates = select_plugin_crates(&self.base.crates, &self.base.exclude)?;
+        let config = load_config()?;
 
         let all_pkg_platforms =
             get_swc_pkg_files(&artifacts_dir).context("failed to get swc package files")?;
 
         info!("Using {:?}", all_pkg_platforms);
 
+        let mut publish_steps = vec![];
+        let mut write_steps = vec![];
+
         for (crate_name, manifest_dir) in crates {
             let base_package_json_path = manifest_dir.join("package.json");
-            assert!(
-                base_package_json_path.is_file(),
-                "package.json for crate `{}` is not found",
-                crate_name
-            );
 
-            let base_package_json_str = read_to_string(&base_package_json_path).context(
-                format!("failed to read `{}`", base_package_json_path.display()),
-            )?;
+            let mut base_package_json =
+                check_package_json(&base_package_json_path, &crate_name, &config.naming)?;
+
+            let mut pkg_platforms = all_pkg_platforms
+                .get(&crate_name)
+                .with_context(|| format!("failed to get package files for crate `{}`", crate_name))?
+                .clone();
+            let has_fat_package = pkg_platforms.iter().any(|p| p == FAT_PACKAGE_PLATFORM);
+            if config.fat_package {
+                if !has_fat_package {
+                    bail!(
+                        "`fat-package` is enabled but `{}.{}.swc-pkg.tgz` does not exist",
+                        crate_name,
+                        FAT_PACKAGE_PLATFORM
+                    )
+                }
+                pkg_platforms.retain(|p| p == FAT_PACKAGE_PLATFORM);
+            } else {
+                pkg_platforms.retain(|p| p != FAT_PACKAGE_PLATFORM);
+            }
 
-            let mut base_package_json = Value::from_str(&base_package_json_str)?;
-            if !base_package_json.is_object() {
-                bail!("package.json is not an object")
+            // The universal package replaces thin packages for macOS.
+            let has_universal = pkg_platforms.iter().any(|p| p == UNIVERSAL_DARWIN);
+            if has_universal {
+                pkg_platforms.retain(|platform| {
+                    let thin = THIN_DARWIN.contains(&&**platform);
+                    if thin {
+                        warn!(
+                            "Skipping `{}.{}.swc-pkg.tgz` because `{}` replaces it",
+                            crate_name, platform, UNIVERSAL_DARWIN
+                        );
+                    }
+                    !thin
+                });
             }
 
-            let pkg_platforms = all_pkg_platforms.get(&crate_name).with_context(|| {
-                format!("failed to get package files for crate `{}`", crate_name)
+            // The package for all platforms is a normal dependency.
+            let deps_field = if config.fat_package {
+                "dependencies"
+            } else {
+                "optionalDependencies"
+            };
+            let original_deps = base_package_json[deps_field].clone();
+
+            // The tag is derived on each run, so a tag of a previous release is never
+            // reused.
+            let pkg_version = base_package_json["version"].as_str().unwrap().to_string();
+            let tag = match &self.base.tag {
+                Some(tag) => tag.clone(),
+                None => default_dist_tag(&pkg_version).to_string(),
+            };
+            set_publish_config_tag(&mut base_package_json, &tag).with_context(|| {
+                format!(
+                    "failed to set `publishConfig.tag` of `{}`",
+                    base_package_json_path.display()
+                )
             })?;
 
             {
+                // `check_package_json` ensures that these fields exist.
                 let pkg_json_obj = base_package_json.as_object_mut().unwrap();
+                let pkg_name = pkg_json_obj["name"].as_str().unwrap().to_string();
 
-                if !pkg_json_obj.contains_key("optionalDependencies") {
-                    pkg_json_obj.insert(
-                        "optionalDependencies".to_string(),
-                        Value::Object(Default::default()),
-                    );
-                }
-
-                let pkg_name = pkg_json_obj
-                    .get("name")
-                    .unwrap()
-                    .as_str()
-                    .unwrap()
-                    .to_string();
-                let pkg_version = pkg_json_obj
-                    .get("version")
-                    .unwrap()
-                    .as_str()
-                    .unwrap()
-                    .to_string();
-
-                let opt_deps = pkg_json_obj
-                    .get_mut("optionalDependencies")
-                    .unwrap()
+                let deps = pkg_json_obj
+                    .entry(deps_field)
+                    .or_insert_with(|| Value::Object(Default::default()))
                     .as_object_mut()
-                    .unwrap();
+                    .with_context(|| {
+                        format!(
+                            "`{}` of `{}` should be an object",
+                            deps_field,
+                            base_package_json_path.display()
+                        )
+                    })?;
+
+                if has_universal {
+                    for platform in THIN_DARWIN {
+                        let platform = parse_node_platform(platform)?;
+                        deps.remove(
+                            &config
+                                .naming
+                                .package_name(&pkg_name, &crate_name, &platform),
+                        );
+                    }
+                }
 
                 for platform in pkg_platforms.iter() {
-                    let dep_name = format!("{}-{}", pkg_name, platform);
-                    if !opt_deps.contains_key(&dep_name) {
-                        opt_deps.insert(dep_name, Value::String(pkg_version.clone()));
+                    let dep_name = if config.fat_package {
+                        config.naming.fat_package_name(&pkg_name, &crate_name)
+                    } else {
+                        let platform = parse_node_platform(platform)?;
+                        config
+                            .naming
+                            .package_name(&pkg_name, &crate_name, &platform)
+                    };
+                    if !deps.contains_key(&dep_name) {
+                        deps.insert(dep_name, Value::String(pkg_version.clone()));
                     }
                 }
             }
-            let pkg_json_str = serde_json::to_string_pretty(&base_package_json)?;
 
-            fs::write(&base_package_json_path, &pkg_json_str).context(format!(
-                "failed to write to `{}`",
-                base_package_json_path.display()
-            ))?;
+            if self.dry_run {
+                println!("{}", base_package_json_path.display());
+                println!("  tag: {}", tag);
+                println!("  {}:", deps_field);
+                let lines = json_diff(&original_deps, &base_package_json[deps_field]);
+                if lines.is_empty() {
+                    println!("    (no changes)");
+                }
+                for line in lines {
+                    println!("    {}", line);
+                }
+
+                println!("  packages to publish:");
+                for platform in pkg_platforms.iter() {
+                    let path =
+                        artifacts_dir.join(format!("{}.{}.swc-pkg.tgz", crate_name, platform));
+                    let pkg = read_package_json_in_tarball(&path)?;
+                    println!(
+                        "    {}@{} ({}) from {}",
+                        pkg["name"].as_str().unwrap_or("<unknown>"),
+                        pkg["version"].as_str().unwrap_or("<unknown>"),
+                        platform,
+                        path.display()
+                    );
+                }
+                println!();
+                continue;
+            }
 
             for platform in pkg_platforms.iter() {
-                let platform_pkg_filename = format!("{}.{}.swc-pkg.tgz", crate_name, platform);
+                let tarball =
+                    artifacts_dir.join(format!("{}.{}.swc-pkg.tgz", crate_name, platform));
+                let pkg = read_package_json_in_tarball(&tarball)?;
+                let integrity = integrity_of_file(&tarball)?;
+
+                publish_steps.push(Step {
+                    action: Action::Publish {
+                        crate_name: crate_name.clone(),
+                        platform: platform.clone(),
+                        tarball,
+                        name: pkg["name"].as_str().unwrap_or_default().to_string(),
+                        version: pkg["version"].as_str().unwrap_or_default().to_string(),
+                        tag: tag.clone(),
+                        integrity,
+                    },
+                    done: false,
+                });
+            }
+
+            write_steps.push(Step {
+                action: Action::WritePackageJson {
+                    crate_name: crate_name.clone(),
+                    path: base_package_json_path,
+                    content: serde_json::to_string_pretty(&base_package_json)?,
+                },
+                done: false,
+            });
+        }
+
+        if self.dry_run {
+            return Ok(());
+        }
+
+        // `package.json` files are modified only after all packages are published.
+        publish_steps.extend(write_steps);
+        let state = PublishState {
+            access,
+            use_npm: self.npm || config.registry.use_npm,
+            steps: publish_steps,
+        };
+        state.save()?;
 
-                publish_tarball_to_npm(
-                    &artifacts_dir.join(&platform_pkg_filename),
-                    access.as_deref(),
+        execute(state)
+    }
+}
+
+/// Runs steps which are not done, recording progress in the state file.
+fn execute(mut state: PublishState) -> Result<()> {
+    let config = load_config()?;
+    let npmrc = Npmrc::load()?;
+
+    for i in 0..state.steps.len() {
+        if state.steps[i].done {
+            continue;
+        }
+
+        match &state.steps[i].action {
+            Action::Publish {
+                crate_name,
+                platform,
+                tarball,
+                name,
+                version,
+                tag,
+                integrity,
+            } => {
+                if integrity_of_file(tarball)? != *integrity {
+                    bail!(
+                        "`{}` was modified after the publish was planned. Remove `{}` to start \
+                         over",
+                        tarball.display(),
+                        PublishState::path()?.display()
+                    )
+                }
+
+                let registry = NpmRegistry::for_package(name, &config.registry, &npmrc);
+                let published = publish_unless_published(
+                    &registry,
+                    tarball,
+                    name,
+                    version,
+                    state.access.as_deref(),
+                    tag,
+                    state.use_npm,
                 )
                 .with_context(|| {
                     format!(
-                        "failed to publish platform package for `{}` (target = {})",
+                        "failed to publish platform package for `{}` (target = {}). Fix the \
+                         problem and run `publish-deps --resume`",
                         crate_name, platform
                     )
                 })?;
+
+                if published {
+                    info!("Published {}@{}", name, version);
+                } else {
+                    info!(
+                        "Skipping {}@{} because it's already published",
+                        name, version
+                    );
+                }
+            }
+            Action::WritePackageJson { path, content, .. } => {
+                fs::write(path, content)
+                    .with_context(|| format!("failed to write to `{}`", path.display()))?;
             }
         }
 
-        Ok(())
+        state.steps[i].done = true;
+        state.save()?;
     }
+
+    PublishState::remove()
+}
+
+/// Publishes `tarball` unless `name@version` exists in the registry, so a
+/// release can be repeated.
+///
+/// Returns `false` if it's skipped because the published package file is
+/// identical. Fails if the published one is different.
+fn publish_unless_published(
+    registry: &NpmRegistry,
+    tarball: &Path,
+    name: &str,
+    version: &str,
+    access: Option<&str>,
+    tag: &str,
+    use_npm: bool,
+) -> Result<bool> {
+    if let Some(dist) = registry.published_dist(name, version)? {
+        let bytes =
+            fs::read(tarball).with_context(|| format!("failed to read `{}`", tarball.display()))?;
+        if !dist.matches(&bytes) {
+            bail!(
+                "{}@{} is already published, but `{}` is different from it. Bump the version to \
+                 publish it",
+                name,
+                version,
+                tarball.display()
+            )
+        }
+
+        return Ok(false);
+    }
+
+    if use_npm {
+        publish_tarball_to_npm(tarball, access, tag)?;
+    } else {
+        registry.publish(tarball, access, tag)?;
+    }
+
+    Ok(true)
+}
+
+/// `npm publish` of the main package reads `publishConfig.tag`, so it's set to
+/// keep the tag of the main package and platform packages same. It's removed
+/// for `latest`, which is the default of npm.
+fn set_publish_config_tag(package_json: &mut Value, tag: &str) -> Result<()> {
+    let pkg_json_obj = package_json.as_object_mut().unwrap();
+
+    if tag == "latest" {
+        if let Some(publish_config) = pkg_json_obj.get_mut("publishConfig") {
+            if let Some(obj) = publish_config.as_object_mut() {
+                obj.remove("tag");
+                if obj.is_empty() {
+                    pkg_json_obj.remove("publishConfig");
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    pkg_json_obj
+        .entry("publishConfig")
+        .or_insert_with(|| Value::Object(Default::default()))
+        .as_object_mut()
+        .context("`publishConfig` should be an object")?
+        .insert("tag".into(), Value::String(tag.to_string()));
+
+    Ok(())
+}
+
+fn integrity_of_file(path: &Path) -> Result<String> {
+    let bytes = fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+    Ok(integrity_of(&bytes))
 }
 
 /// Key is crate name and values are platforms
@@ -154,5 +469,119 @@ fn get_swc_pkg_files(artifacts_dir: &Path) -> Result<AHashMap<String, Vec<String
         }
     }
 
+    for platforms in buf.values_mut() {
+        platforms.sort();
+    }
+
     Ok(buf)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::node::stub_registry::{create_tarball, StubRegistry};
+    use serde_json::json;
+
+    #[test]
+    fn publish_absent_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let tarball = create_tarball(dir.path(), "plugin-linux-x64-gnu", "1.0.0", "a");
+        let stub = StubRegistry::start();
+        let registry = NpmRegistry::new(stub.url(), Some("secret".into()));
+
+        let published = publish_unless_published(
+            &registry,
+            &tarball.path,
+            "plugin-linux-x64-gnu",
+            "1.0.0",
+            None,
+            "latest",
+            false,
+        )
+        .unwrap();
+        assert!(published);
+
+        let packument = stub.packument("plugin-linux-x64-gnu").unwrap();
+        assert_eq!(
+            packument["versions"]["1.0.0"]["dist"]["integrity"],
+            tarball.integrity
+        );
+    }
+
+    #[test]
+    fn skip_identical_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let tarball = create_tarball(dir.path(), "plugin-linux-x64-gnu", "1.0.0", "a");
+        let stub = StubRegistry::start();
+        stub.add_version(
+            "plugin-linux-x64-gnu",
+            "1.0.0",
+            json!({ "integrity": tarball.integrity }),
+        );
+        let registry = NpmRegistry::new(stub.url(), Some("secret".into()));
+
+        let published = publish_unless_published(
+            &registry,
+            &tarball.path,
+            "plugin-linux-x64-gnu",
+            "1.0.0",
+            None,
+            "latest",
+            false,
+        )
+        .unwrap();
+        assert!(!published);
+        assert!(stub.requests().iter().all(|req| req.method == "GET"));
+    }
+
+    #[test]
+    fn fail_on_different_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let published = create_tarball(dir.path(), "plugin-linux-x64-gnu", "1.0.0", "a");
+        let local = create_tarball(
+            &dir.path().join("local"),
+            "plugin-linux-x64-gnu",
+            "1.0.0",
+            "b",
+        );
+        let stub = StubRegistry::start();
+        stub.add_version(
+            "plugin-linux-x64-gnu",
+            "1.0.0",
+            json!({ "integrity": published.integrity }),
+        );
+        let registry = NpmRegistry::new(stub.url(), Some("secret".into()));
+
+        let err = publish_unless_published(
+            &registry,
+            &local.path,
+            "plugin-linux-x64-gnu",
+            "1.0.0",
+            None,
+            "latest",
+            false,
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("plugin-linux-x64-gnu@1.0.0 is already published, but"),
+            "{}",
+            err
+        );
+        assert!(stub.requests().iter().all(|req| req.method == "GET"));
+    }
+
+    #[test]
+    fn publish_config_tag_is_not_kept() {
+        let mut package_json = json!({ "name": "plugin", "version": "1.0.0-canary.1" });
+        set_publish_config_tag(&mut package_json, "canary").unwrap();
+        assert_eq!(package_json["publishConfig"], json!({ "tag": "canary" }));
+
+        set_publish_config_tag(&mut package_json, "latest").unwrap();
+        assert_eq!(package_json.get("publishConfig"), None);
+
+        let mut package_json = json!({ "publishConfig": { "access": "public", "tag": "next" } });
+        set_publish_config_tag(&mut package_json, "latest").unwrap();
+        assert_eq!(package_json["publishConfig"], json!({ "access": "public" }));
+    }
+}
@@ -0,0 +1,109 @@
+use crate::util::cargo::swc_output_dir;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{create_dir_all, read_to_string, remove_file, rename, write},
+    path::PathBuf,
+};
+
+/// Plan of `publish-deps`, which is stored in `.swc/publish-state.json`.
+///
+/// Each step is marked as done as soon as it succeeds, so a failed run can be
+/// continued with `--resume`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct PublishState {
+    pub access: Option<String>,
+    /// Publish using the npm cli instead of the registry API.
+    #[serde(default)]
+    pub use_npm: bool,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct Step {
+    pub action: Action,
+    pub done: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub(super) enum Action {
+    /// Publish a package file.
+    #[serde(rename_all = "camelCase")]
+    Publish {
+        #[serde(rename = "crate")]
+        crate_name: String,
+        platform: String,
+        tarball: PathBuf,
+        name: String,
+        version: String,
+        #[serde(default = "default_tag")]
+        tag: String,
+        /// Subresource integrity of the package file when the plan was
+        /// created.
+        integrity: String,
+    },
+    /// Write the main `package.json` of a crate.
+    #[serde(rename_all = "camelCase")]
+    WritePackageJson {
+        #[serde(rename = "crate")]
+        crate_name: String,
+        path: PathBuf,
+        content: String,
+    },
+}
+
+fn default_tag() -> String {
+    "latest".into()
+}
+
+impl PublishState {
+    pub fn path() -> Result<PathBuf> {
+        Ok(swc_output_dir()?.join("publish-state.json"))
+    }
+
+    /// Returns `None` if there's no state file.
+    pub fn load() -> Result<Option<Self>> {
+        let path = Self::path()?;
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let s = read_to_string(&path)
+            .with_context(|| format!("failed to read `{}`", path.display()))?;
+        let state = serde_json::from_str(&s)
+            .with_context(|| format!("failed to parse `{}`", path.display()))?;
+        Ok(Some(state))
+    }
+
+    /// Saves the state atomically.
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path()?;
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)?;
+        }
+
+        let tmp_path = path.with_extension("json.tmp");
+        write(&tmp_path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("failed to write `{}`", tmp_path.display()))?;
+        rename(&tmp_path, &path)
+            .with_context(|| format!("failed to write `{}`", path.display()))?;
+
+        Ok(())
+    }
+
+    pub fn remove() -> Result<()> {
+        let path = Self::path()?;
+        if path.is_file() {
+            remove_file(&path).with_context(|| format!("failed to remove `{}`", path.display()))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.steps.iter().all(|s| s.done)
+    }
+}
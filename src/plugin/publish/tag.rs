@@ -0,0 +1,109 @@
+use crate::util::{
+    cargo::select_plugin_crates,
+    config::load_config,
+    node::{
+        add_dist_tag_with_npm,
+        platform::all_node_platforms,
+        registry::{validate_dist_tag, NpmRegistry, Npmrc},
+        validate::check_package_json,
+    },
+};
+use anyhow::{bail, Context, Result};
+use structopt::StructOpt;
+use tracing::info;
+
+/// Point a dist-tag to a version of the main package and its platform
+/// packages.
+///
+/// All packages are checked to be published before any tag is changed.
+#[derive(Debug, StructOpt)]
+pub struct TagCommand {
+    /// Defaults to all plugin crates in the workspace.
+    #[structopt(long)]
+    pub crates: Vec<String>,
+
+    /// Crates to skip.
+    #[structopt(long)]
+    pub exclude: Vec<String>,
+
+    /// Defaults to `version` of the main `package.json`.
+    #[structopt(long)]
+    pub version: Option<String>,
+
+    /// Use `npm dist-tag add` instead of the built-in registry client.
+    #[structopt(long)]
+    pub npm: bool,
+
+    /// Dist-tag to move, like `latest`.
+    pub tag: String,
+}
+
+impl TagCommand {
+    pub fn run(self) -> Result<()> {
+        validate_dist_tag(&self.tag)?;
+
+        let crates = select_plugin_crates(&self.crates, &self.exclude)?;
+        let config = load_config()?;
+        let npmrc = Npmrc::load()?;
+
+        // Platform packages come before the main package of each crate.
+        let mut packages = vec![];
+        for (crate_name, manifest_dir) in crates {
+            let package_json_path = manifest_dir.join("package.json");
+            let package_json = check_package_json(&package_json_path, &crate_name, &config.naming)?;
+
+            // `check_package_json` ensures that these fields exist.
+            let name = package_json["name"].as_str().unwrap().to_string();
+            let version = match &self.version {
+                Some(v) => v.clone(),
+                None => package_json["version"].as_str().unwrap().to_string(),
+            };
+
+            let mut platform_packages = all_node_platforms()
+                .iter()
+                .map(|p| config.naming.package_name(&name, &crate_name, p))
+                .collect::<Vec<_>>();
+            platform_packages.push(config.naming.fat_package_name(&name, &crate_name));
+
+            for field in &["optionalDependencies", "dependencies"] {
+                if let Some(deps) = package_json[field].as_object() {
+                    for dep in deps.keys() {
+                        if platform_packages.contains(dep) {
+                            packages.push((dep.clone(), version.clone()));
+                        }
+                    }
+                }
+            }
+
+            packages.push((name, version));
+        }
+
+        let registries = packages
+            .iter()
+            .map(|(name, _)| NpmRegistry::for_package(name, &config.registry, &npmrc))
+            .collect::<Vec<_>>();
+
+        for ((name, version), registry) in packages.iter().zip(&registries) {
+            if registry.published_dist(name, version)?.is_none() {
+                bail!(
+                    "{}@{} is not published. Tags of other packages are not changed",
+                    name,
+                    version
+                )
+            }
+        }
+
+        for ((name, version), registry) in packages.iter().zip(&registries) {
+            if self.npm || config.registry.use_npm {
+                add_dist_tag_with_npm(name, version, &self.tag)
+            } else {
+                registry.add_dist_tag(name, version, &self.tag)
+            }
+            .with_context(|| format!("failed to move tag `{}` of {}", self.tag, name))?;
+
+            info!("Tagged {}@{} as `{}`", name, version, self.tag);
+        }
+
+        Ok(())
+    }
+}
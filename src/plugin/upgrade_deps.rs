@@ -9,13 +9,17 @@ pub struct UpgradeDepsCommand {
     /// Run upgrade command for all crates in the current workspace.
     #[structopt(long)]
     pub workspace: bool,
+
+    /// Crates to skip. Used with `--workspace`.
+    #[structopt(long)]
+    pub exclude: Vec<String>,
 }
 
 impl UpgradeDepsCommand {
     pub fn run(self) -> Result<(), Error> {
         for crate_name in &["swc_atoms", "swc_common", "swc_plugin"] {
             info!("Upgrading {}", crate_name);
-            upgrade_dep(&crate_name, self.workspace)?;
+            upgrade_dep(&crate_name, self.workspace, &self.exclude)?;
         }
 
         Ok(())
@@ -0,0 +1,367 @@
+use anyhow::{anyhow, bail, Result};
+use std::{convert::TryInto, fmt};
+use swc_node_arch::{NodeArch, NodePlatform, PlatformDetail};
+
+const MH_MAGIC_64: u32 = 0xfeed_facf;
+const MH_MAGIC: u32 = 0xfeed_face;
+const FAT_MAGIC: u32 = 0xcafe_babe;
+
+const CPU_TYPE_X86: u32 = 0x0000_0007;
+const CPU_TYPE_X86_64: u32 = 0x0100_0007;
+const CPU_TYPE_ARM: u32 = 0x0000_000c;
+const CPU_TYPE_ARM64: u32 = 0x0100_000c;
+
+/// Size of `fat_header`.
+const FAT_HEADER_SIZE: usize = 8;
+/// Size of `fat_arch`.
+const FAT_ARCH_SIZE: usize = 20;
+
+/// `cputype` and `cpusubtype` of a thin Mach-O binary.
+fn macho_cpu_type(bytes: &[u8]) -> Result<(u32, u32)> {
+    if bytes.len() < 12 {
+        bail!("file is too small to be a Mach-O binary")
+    }
+
+    let magic = u32::from_le_bytes(bytes[0..4].try_into().unwrap());
+    if magic != MH_MAGIC_64 && magic != MH_MAGIC {
+        if u32::from_be_bytes(bytes[0..4].try_into().unwrap()) == FAT_MAGIC {
+            bail!("file is already a universal binary")
+        }
+        bail!("file is not a little-endian Mach-O binary")
+    }
+
+    Ok((
+        u32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+        u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+    ))
+}
+
+/// Creates an universal binary (fat Mach-O) from thin Mach-O binaries, like
+/// `lipo -create`.
+pub fn create_universal_binary(thin_binaries: &[&[u8]]) -> Result<Vec<u8>> {
+    let mut archs: Vec<(u32, u32, u32, &[u8])> = vec![];
+    for bytes in thin_binaries {
+        let (cpu_type, cpu_subtype) = macho_cpu_type(bytes)?;
+
+        if archs.iter().any(|&(ty, ..)| ty == cpu_type) {
+            bail!("multiple binaries have the same cpu type ({:#x})", cpu_type)
+        }
+
+        // Same as `lipo`
+        let align = match cpu_type {
+            CPU_TYPE_ARM64 => 14,
+            CPU_TYPE_X86_64 => 12,
+            _ => 14,
+        };
+
+        archs.push((cpu_type, cpu_subtype, align, *bytes));
+    }
+    archs.sort_by_key(|&(ty, _, align, _)| (align, ty));
+
+    let mut header = vec![];
+    header.extend_from_slice(&FAT_MAGIC.to_be_bytes());
+    header.extend_from_slice(&(archs.len() as u32).to_be_bytes());
+
+    let mut offset = FAT_HEADER_SIZE + FAT_ARCH_SIZE * archs.len();
+    let mut offsets = vec![];
+    for &(cpu_type, cpu_subtype, align, bytes) in &archs {
+        let alignment = 1usize << align;
+        offset = (offset + alignment - 1) / alignment * alignment;
+        offsets.push(offset);
+
+        header.extend_from_slice(&cpu_type.to_be_bytes());
+        header.extend_from_slice(&cpu_subtype.to_be_bytes());
+        header.extend_from_slice(&(offset as u32).to_be_bytes());
+        header.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        header.extend_from_slice(&align.to_be_bytes());
+
+        offset += bytes.len();
+    }
+
+    let mut buf = header;
+    for (&(.., bytes), &offset) in archs.iter().zip(offsets.iter()) {
+        buf.resize(offset, 0);
+        buf.extend_from_slice(bytes);
+    }
+
+    Ok(buf)
+}
+
+/// Format of an executable file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryFormat {
+    Elf,
+    MachO,
+    Pe,
+}
+
+impl BinaryFormat {
+    /// Format of dynamic libraries for `platform`.
+    pub fn of(platform: NodePlatform) -> Self {
+        match platform {
+            NodePlatform::Darwin => BinaryFormat::MachO,
+            NodePlatform::Windows => BinaryFormat::Pe,
+            NodePlatform::Android | NodePlatform::Linux | NodePlatform::Freebsd => {
+                BinaryFormat::Elf
+            }
+        }
+    }
+}
+
+impl fmt::Display for BinaryFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BinaryFormat::Elf => "ELF",
+            BinaryFormat::MachO => "Mach-O",
+            BinaryFormat::Pe => "PE",
+        })
+    }
+}
+
+/// Detects the format and architectures of an executable file from its
+/// headers.
+///
+/// A universal binary of macOS has multiple architectures.
+pub fn detect_binary(bytes: &[u8]) -> Result<(BinaryFormat, Vec<NodeArch>)> {
+    let u16_at = |at: usize, le: bool| -> Option<u16> {
+        let b = bytes.get(at..at + 2)?.try_into().unwrap();
+        Some(if le {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    };
+    let u32_at = |at: usize, le: bool| -> Option<u32> {
+        let b = bytes.get(at..at + 4)?.try_into().unwrap();
+        Some(if le {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    };
+
+    if bytes.starts_with(b"\x7fELF") {
+        let is_64 = bytes.get(4) == Some(&2);
+        let le = bytes.get(5) == Some(&1);
+        let machine = match u16_at(18, le) {
+            Some(v) => v,
+            None => bail!("ELF header is truncated"),
+        };
+
+        let arch = match machine {
+            0x03 => NodeArch::Ia32,
+            0x08 if le => NodeArch::MipSel,
+            0x08 => NodeArch::Mips,
+            0x14 => NodeArch::Ppc,
+            0x15 => NodeArch::Ppc64,
+            0x16 if is_64 => NodeArch::S390x,
+            0x16 => NodeArch::S390,
+            0x28 => NodeArch::Arm,
+            0x3e if is_64 => NodeArch::X64,
+            0x3e => NodeArch::X32,
+            0xb7 => NodeArch::Arm64,
+            _ => bail!("unknown ELF machine ({:#x})", machine),
+        };
+        return Ok((BinaryFormat::Elf, vec![arch]));
+    }
+
+    if bytes.starts_with(b"MZ") {
+        let pe_offset = match u32_at(0x3c, true) {
+            Some(v) => v as usize,
+            None => bail!("DOS header is truncated"),
+        };
+        if bytes.get(pe_offset..pe_offset + 4) != Some(b"PE\0\0") {
+            bail!("file does not have a PE header")
+        }
+
+        let machine = match u16_at(pe_offset + 4, true) {
+            Some(v) => v,
+            None => bail!("PE header is truncated"),
+        };
+        let arch = match machine {
+            0x014c => NodeArch::Ia32,
+            0x01c4 => NodeArch::Arm,
+            0x8664 => NodeArch::X64,
+            0xaa64 => NodeArch::Arm64,
+            _ => bail!("unknown PE machine ({:#x})", machine),
+        };
+        return Ok((BinaryFormat::Pe, vec![arch]));
+    }
+
+    if u32_at(0, false) == Some(FAT_MAGIC) {
+        let count = u32_at(4, false).unwrap_or(0) as usize;
+        let mut archs = vec![];
+        for i in 0..count {
+            let cpu_type = match u32_at(FAT_HEADER_SIZE + FAT_ARCH_SIZE * i, false) {
+                Some(v) => v,
+                None => bail!("fat header is truncated"),
+            };
+            archs.push(macho_arch(cpu_type)?);
+        }
+        return Ok((BinaryFormat::MachO, archs));
+    }
+
+    let (cpu_type, _) =
+        macho_cpu_type(bytes).map_err(|_| anyhow!("file is not an ELF, Mach-O or PE binary"))?;
+    Ok((BinaryFormat::MachO, vec![macho_arch(cpu_type)?]))
+}
+
+fn macho_arch(cpu_type: u32) -> Result<NodeArch> {
+    Ok(match cpu_type {
+        CPU_TYPE_X86 => NodeArch::Ia32,
+        CPU_TYPE_X86_64 => NodeArch::X64,
+        CPU_TYPE_ARM => NodeArch::Arm,
+        CPU_TYPE_ARM64 => NodeArch::Arm64,
+        _ => bail!("unknown Mach-O cpu type ({:#x})", cpu_type),
+    })
+}
+
+/// Checks if `bytes` is a binary for `platform`.
+pub fn verify_binary(bytes: &[u8], platform: &PlatformDetail) -> Result<()> {
+    let (format, mut archs) = detect_binary(bytes)?;
+
+    let expected_format = BinaryFormat::of(platform.platform);
+    if format != expected_format {
+        bail!(
+            "expected a {} binary for `{}`, but found a {} binary",
+            expected_format,
+            platform,
+            format
+        )
+    }
+
+    let mut expected_archs = platform.arch.cpus();
+    archs.sort();
+    expected_archs.sort();
+    if archs != expected_archs {
+        bail!(
+            "expected a binary for {:?}, but found a binary for {:?}",
+            expected_archs
+                .iter()
+                .map(|a| a.to_string())
+                .collect::<Vec<_>>(),
+            archs.iter().map(|a| a.to_string()).collect::<Vec<_>>()
+        )
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::node::platform::parse_node_platform;
+
+    /// A thin 64-bit Mach-O header followed by `payload`.
+    fn thin(cpu_type: u32, cpu_subtype: u32, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&MH_MAGIC_64.to_le_bytes());
+        bytes.extend_from_slice(&cpu_type.to_le_bytes());
+        bytes.extend_from_slice(&cpu_subtype.to_le_bytes());
+        bytes.resize(32, 0);
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    fn be_u32(bytes: &[u8], at: usize) -> u32 {
+        u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    fn elf_x64() -> Vec<u8> {
+        let mut bytes = b"\x7fELF\x02\x01".to_vec();
+        bytes.resize(64, 0);
+        bytes[18..20].copy_from_slice(&0x3eu16.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn universal_binary_layout() {
+        let arm64 = thin(CPU_TYPE_ARM64, 0, b"arm64");
+        let x64 = thin(CPU_TYPE_X86_64, 3, b"x86_64");
+
+        let fat = create_universal_binary(&[&arm64, &x64]).unwrap();
+
+        assert_eq!(&fat[0..4], &[0xca, 0xfe, 0xba, 0xbe]);
+        assert_eq!(be_u32(&fat, 4), 2);
+
+        // Sorted by alignment like `lipo`, so x86_64 comes first.
+        let arch = |i: usize| {
+            let at = FAT_HEADER_SIZE + FAT_ARCH_SIZE * i;
+            (0..5).map(|j| be_u32(&fat, at + 4 * j)).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            arch(0),
+            vec![CPU_TYPE_X86_64, 3, 0x1000, x64.len() as u32, 12]
+        );
+        assert_eq!(
+            arch(1),
+            vec![CPU_TYPE_ARM64, 0, 0x4000, arm64.len() as u32, 14]
+        );
+
+        assert_eq!(&fat[0x1000..0x1000 + x64.len()], &*x64);
+        assert_eq!(&fat[0x4000..], &*arm64);
+        assert!(fat[0x1000 + x64.len()..0x4000].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn universal_binary_round_trip() {
+        let arm64 = thin(CPU_TYPE_ARM64, 0, b"arm64");
+        let x64 = thin(CPU_TYPE_X86_64, 3, b"x86_64");
+        let fat = create_universal_binary(&[&x64, &arm64]).unwrap();
+
+        assert_eq!(
+            detect_binary(&fat).unwrap(),
+            (BinaryFormat::MachO, vec![NodeArch::X64, NodeArch::Arm64])
+        );
+        verify_binary(&fat, &parse_node_platform("darwin-universal").unwrap()).unwrap();
+        assert!(verify_binary(&fat, &parse_node_platform("darwin-arm64").unwrap()).is_err());
+
+        // A fat binary can't be merged again.
+        assert!(create_universal_binary(&[&fat, &x64]).is_err());
+    }
+
+    #[test]
+    fn invalid_universal_binary_inputs() {
+        let x64 = thin(CPU_TYPE_X86_64, 3, b"a");
+        let other_x64 = thin(CPU_TYPE_X86_64, 3, b"b");
+        assert!(create_universal_binary(&[&x64, &other_x64]).is_err());
+
+        assert!(create_universal_binary(&[&x64, &elf_x64()]).is_err());
+        assert!(create_universal_binary(&[&x64, b"short"]).is_err());
+    }
+
+    #[test]
+    fn detect_thin_binaries() {
+        assert_eq!(
+            detect_binary(&thin(CPU_TYPE_ARM64, 0, b"")).unwrap(),
+            (BinaryFormat::MachO, vec![NodeArch::Arm64])
+        );
+        assert_eq!(
+            detect_binary(&elf_x64()).unwrap(),
+            (BinaryFormat::Elf, vec![NodeArch::X64])
+        );
+
+        let mut pe = b"MZ".to_vec();
+        pe.resize(0x80, 0);
+        pe[0x3c..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+        pe[0x40..0x44].copy_from_slice(b"PE\0\0");
+        pe[0x44..0x46].copy_from_slice(&0xaa64u16.to_le_bytes());
+        assert_eq!(
+            detect_binary(&pe).unwrap(),
+            (BinaryFormat::Pe, vec![NodeArch::Arm64])
+        );
+
+        assert!(detect_binary(b"#!/bin/sh").is_err());
+    }
+
+    #[test]
+    fn verify_platform_of_binary() {
+        let linux = parse_node_platform("linux-x64-gnu").unwrap();
+        let darwin = parse_node_platform("darwin-x64").unwrap();
+
+        verify_binary(&elf_x64(), &linux).unwrap();
+        verify_binary(&thin(CPU_TYPE_X86_64, 3, b""), &darwin).unwrap();
+        assert!(verify_binary(&elf_x64(), &darwin).is_err());
+        assert!(verify_binary(&thin(CPU_TYPE_ARM64, 0, b""), &darwin).is_err());
+    }
+}
@@ -1,10 +1,12 @@
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use cached::proc_macro::cached;
-use cargo_metadata::MetadataCommand;
+use cargo_metadata::{DependencyKind, MetadataCommand, Package};
+use indexmap::IndexSet;
 use std::{
     env,
     path::{Path, PathBuf},
 };
+use tracing::{debug, warn};
 
 pub mod add;
 pub mod upgrade;
@@ -20,10 +22,8 @@ pub fn cargo_metadata(mut cmd: MetadataCommand, from: &Path) -> Result<cargo_met
     Ok(result)
 }
 
-/// Get all crate in cargo workspace.
-///
-/// Returns `(name, manifest_dir)`
-pub fn get_all_crates() -> Result<Vec<(String, PathBuf)>> {
+/// Get all packages in cargo workspace.
+pub fn get_workspace_packages() -> Result<Vec<Package>> {
     (|| -> Result<_> {
         let mut cmd = MetadataCommand::new();
         cmd.no_deps();
@@ -34,24 +34,143 @@ pub fn get_all_crates() -> Result<Vec<(String, PathBuf)>> {
         Ok(md
             .packages
             .into_iter()
-            .filter_map(|p| {
-                if !ws_pkgs.contains(&p.id) {
-                    return None;
-                }
-
-                let manifest_dir = p
-                    .manifest_path
-                    .parent()
-                    .unwrap()
-                    .to_path_buf()
-                    .into_std_path_buf();
-                Some((p.name, manifest_dir))
-            })
+            .filter(|p| ws_pkgs.contains(&p.id))
             .collect())
     })()
     .context("failed to get members of the cargo workspace")
 }
 
+pub fn manifest_dir_of(p: &Package) -> PathBuf {
+    p.manifest_path
+        .parent()
+        .unwrap()
+        .to_path_buf()
+        .into_std_path_buf()
+}
+
+/// Returns `true` if `p` has a target with `cdylib` crate type.
+pub fn has_cdylib(p: &Package) -> bool {
+    p.targets
+        .iter()
+        .any(|t| t.crate_types.iter().any(|ty| ty == "cdylib"))
+}
+
+/// Checks if `p` is a plugin crate.
+///
+/// A plugin crate has a lib target with `cdylib` crate type and a
+/// `package.json` next to `Cargo.toml`. The returned error explains why the
+/// crate is not a plugin crate.
+///
+/// If `lenient` is `true`, either of them is enough. This is used to lint
+/// crates which are not fully set up yet.
+fn check_plugin_crate(p: &Package, lenient: bool) -> Result<()> {
+    let manifest_dir = manifest_dir_of(p);
+    let has_package_json = manifest_dir.join("package.json").is_file();
+
+    if lenient {
+        if !has_cdylib(p) && !has_package_json {
+            bail!(
+                "`{}` is not a plugin crate because it has neither a lib target with `crate-type \
+                 = [\"cdylib\"]` nor `package.json` in `{}`",
+                p.name,
+                manifest_dir.display()
+            )
+        }
+
+        return Ok(());
+    }
+
+    if !has_cdylib(p) {
+        bail!(
+            "`{}` is not a plugin crate because it does not have a lib target with `crate-type = \
+             [\"cdylib\"]`",
+            p.name
+        )
+    }
+
+    if !has_package_json {
+        bail!(
+            "`{}` is not a plugin crate because `{}` does not have `package.json`",
+            p.name,
+            manifest_dir.display()
+        )
+    }
+
+    Ok(())
+}
+
+/// Select plugin crates in the cargo workspace.
+///
+/// If `names` is empty, all plugin crates in the workspace are selected.
+/// Otherwise, every crate in `names` should be a plugin crate. Crates in
+/// `exclude` are never selected.
+///
+/// Returns `(name, manifest_dir)`
+pub fn select_plugin_crates(
+    names: &[String],
+    exclude: &[String],
+) -> Result<Vec<(String, PathBuf)>> {
+    Ok(select_crates(names, exclude, false)?
+        .iter()
+        .map(|p| (p.name.clone(), manifest_dir_of(p)))
+        .collect())
+}
+
+/// Like [select_plugin_crates], but also selects crates which have only one
+/// of a `cdylib` target and `package.json`, so they can be linted.
+pub fn select_plugin_crates_to_lint(names: &[String], exclude: &[String]) -> Result<Vec<Package>> {
+    select_crates(names, exclude, true)
+}
+
+fn select_crates(names: &[String], exclude: &[String], lenient: bool) -> Result<Vec<Package>> {
+    let pkgs = get_workspace_packages()?;
+
+    for name in exclude {
+        if !pkgs.iter().any(|p| p.name == *name) {
+            warn!(
+                "`--exclude {}` does not match any crate in the workspace",
+                name
+            );
+        }
+    }
+
+    if names.is_empty() {
+        let mut selected = vec![];
+
+        for p in pkgs {
+            if exclude.contains(&p.name) {
+                continue;
+            }
+
+            match check_plugin_crate(&p, lenient) {
+                Ok(()) => selected.push(p),
+                Err(reason) => debug!("Skipping: {}", reason),
+            }
+        }
+
+        if selected.is_empty() {
+            bail!("failed to find a plugin crate in the cargo workspace")
+        }
+
+        return Ok(selected);
+    }
+
+    names
+        .iter()
+        .filter(|name| !exclude.contains(name))
+        .map(|name| {
+            let p = pkgs
+                .iter()
+                .find(|p| p.name == *name)
+                .ok_or_else(|| anyhow!("`{}` is not a member of the cargo workspace", name))?;
+
+            check_plugin_crate(p, lenient)?;
+
+            Ok(p.clone())
+        })
+        .collect()
+}
+
 pub fn swc_output_dir() -> Result<PathBuf> {
     let cargo_target = cargo_target_dir()?;
 
@@ -75,6 +194,164 @@ pub fn get_cargo_manifest_path(crate_name: String) -> Result<PathBuf> {
         .into_std_path_buf())
 }
 
+/// Get the resolved version of `dep_name`, which is a direct dependency of
+/// `crate_name`.
+#[cached(result)]
+pub fn get_resolved_dep_version(crate_name: String, dep_name: String) -> Result<Option<String>> {
+    (|| -> Result<_> {
+        let from = env::current_dir().context("failed to get current dir")?;
+
+        let cmd = MetadataCommand::new();
+        let md = cargo_metadata(cmd, &from)?;
+
+        let pkg = md
+            .packages
+            .iter()
+            .filter(|p| md.workspace_members.contains(&p.id))
+            .find(|p| p.name == crate_name)
+            .context("failed to find the package")?;
+
+        let node = md
+            .resolve
+            .as_ref()
+            .and_then(|resolve| resolve.nodes.iter().find(|node| node.id == pkg.id))
+            .context("`cargo metadata` does not have the resolved dependency graph")?;
+
+        Ok(node
+            .deps
+            .iter()
+            .filter_map(|dep| md.packages.iter().find(|p| p.id == dep.pkg))
+            .find(|p| p.name == dep_name)
+            .map(|p| p.version.to_string()))
+    })()
+    .with_context(|| {
+        format!(
+            "failed to get the resolved version of `{}` used by `{}`",
+            dep_name, crate_name
+        )
+    })
+}
+
+/// Packages linked into a crate, resolved for a target.
+#[derive(Debug, Clone)]
+pub struct ResolvedDeps {
+    pub root: Package,
+    /// Dependencies linked into the root, excluding the root itself. Sorted by
+    /// the package id.
+    pub packages: Vec<Package>,
+    /// Key is a package id and values are ids of its dependencies.
+    pub edges: Vec<(String, Vec<String>)>,
+}
+
+/// Proc macros are not linked into the binary.
+fn is_proc_macro(p: &Package) -> bool {
+    !p.targets.is_empty()
+        && p.targets
+            .iter()
+            .all(|t| t.kind.iter().any(|k| k == "proc-macro"))
+}
+
+/// Resolves the dependency graph of `crate_name` for `target`, following
+/// normal dependencies.
+///
+/// Proc macros are not followed because they are not linked into the binary.
+///
+/// If `target` is `None`, dependencies for all platforms are included.
+#[cached(result)]
+pub fn resolve_deps(crate_name: String, target: Option<String>) -> Result<ResolvedDeps> {
+    (|| -> Result<_> {
+        let from = env::current_dir().context("failed to get current dir")?;
+
+        let mut cmd = MetadataCommand::new();
+        if let Some(target) = &target {
+            cmd.other_options(vec!["--filter-platform".to_string(), target.clone()]);
+        }
+        let md = cargo_metadata(cmd, &from)?;
+
+        let root = md
+            .packages
+            .iter()
+            .filter(|p| md.workspace_members.contains(&p.id))
+            .find(|p| p.name == crate_name)
+            .context("failed to find the package")?
+            .clone();
+        let resolve = md
+            .resolve
+            .as_ref()
+            .context("`cargo metadata` does not have the resolved dependency graph")?;
+
+        let proc_macros = md
+            .packages
+            .iter()
+            .filter(|p| is_proc_macro(p))
+            .map(|p| &p.id)
+            .collect::<Vec<_>>();
+
+        let mut visited = IndexSet::new();
+        let mut queue = vec![root.id.clone()];
+        let mut edges = vec![];
+
+        while let Some(id) = queue.pop() {
+            if !visited.insert(id.clone()) {
+                continue;
+            }
+
+            let node = match resolve.nodes.iter().find(|node| node.id == id) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            let deps = node
+                .deps
+                .iter()
+                .filter(|dep| {
+                    dep.dep_kinds.is_empty()
+                        || dep
+                            .dep_kinds
+                            .iter()
+                            .any(|k| k.kind == DependencyKind::Normal)
+                })
+                // Proc macros and their dependencies run only at compile time.
+                .filter(|dep| !proc_macros.contains(&&dep.pkg))
+                .map(|dep| dep.pkg.clone())
+                .collect::<Vec<_>>();
+
+            edges.push((
+                id.repr.clone(),
+                deps.iter().map(|id| id.repr.clone()).collect(),
+            ));
+            queue.extend(deps);
+        }
+
+        let mut packages = md
+            .packages
+            .iter()
+            .filter(|p| p.id != root.id && visited.contains(&p.id))
+            .cloned()
+            .collect::<Vec<_>>();
+        packages.sort_by(|a, b| a.id.repr.cmp(&b.id.repr));
+        edges.sort();
+
+        Ok(ResolvedDeps {
+            root,
+            packages,
+            edges,
+        })
+    })()
+    .with_context(|| format!("failed to resolve dependencies of `{}`", crate_name))
+}
+
+#[cached(result)]
+pub fn cargo_workspace_root() -> Result<PathBuf> {
+    let from = env::current_dir().context("failed to get current dir")?;
+
+    let mut cmd = MetadataCommand::new();
+    cmd.no_deps();
+    let md = cargo_metadata(cmd, &from)?;
+
+    Ok(md.workspace_root.as_std_path().to_path_buf())
+}
+
 #[cached(result)]
 pub fn cargo_target_dir() -> Result<PathBuf> {
     let from = env::current_dir().context("failed to get current dir")?;
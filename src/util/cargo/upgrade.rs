@@ -23,8 +23,8 @@ fn is_version_dep(dependency: &cargo_metadata::Dependency) -> bool {
 }
 
 impl Manifests {
-    /// Get all manifests in the workspace.
-    fn get_all(manifest_path: &Option<PathBuf>) -> Result<Self, Error> {
+    /// Get all manifests in the workspace, except crates in `exclude`.
+    fn get_all(manifest_path: &Option<PathBuf>, exclude: &[String]) -> Result<Self, Error> {
         let cur_dir = env::current_dir().context("failed to get current directory")?;
 
         let mut cmd = cargo_metadata::MetadataCommand::new();
@@ -37,6 +37,7 @@ impl Manifests {
         result
             .packages
             .into_iter()
+            .filter(|package| !exclude.contains(&package.name))
             .map(|package| {
                 Ok((
                     LocalManifest::try_new(Path::new(&package.manifest_path))
@@ -260,9 +261,9 @@ impl DesiredUpgrades {
 struct ActualUpgrades(HashMap<Dependency, String>);
 
 /// `cargo upgrade`, from `cargo-edit`.
-pub fn upgrade_dep(crate_name: &str, workspace: bool) -> Result<(), Error> {
+pub fn upgrade_dep(crate_name: &str, workspace: bool, exclude: &[String]) -> Result<(), Error> {
     let manifests = if workspace {
-        Manifests::get_all(&None)
+        Manifests::get_all(&None, exclude)
     } else {
         Manifests::get_local_one(&None)
     }
@@ -0,0 +1,273 @@
+use crate::util::cargo::cargo_metadata;
+use anyhow::{bail, Context, Result};
+use cached::proc_macro::cached;
+use cargo_metadata::MetadataCommand;
+use serde::Deserialize;
+use serde_json::Value;
+use std::env;
+use swc_node_arch::PlatformDetail;
+
+/// Configuration of `swc-dev`.
+///
+/// This is read from `[workspace.metadata.swc-dev]` of the workspace root, or
+/// from `[package.metadata.swc-dev]` if the root is not a workspace.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// Native libraries which plugins are allowed to link dynamically.
+    ///
+    /// If this is specified, `plugin build` fails when a build script links a
+    /// library not in this list. Static libraries are bundled into the plugin
+    /// and are not checked, and neither are build scripts of build
+    /// dependencies and proc macros.
+    pub allowed_native_libs: Option<Vec<String>>,
+
+    /// Fields of `package.json` for binary packages.
+    pub package_json: PackageJsonConfig,
+
+    /// Versions of `@swc/core` compatible with each version of `swc_plugin`.
+    ///
+    /// The first entry matching the resolved version of `swc_plugin` is added
+    /// to `peerDependencies` of binary packages.
+    pub swc_core_peer: Vec<SwcCorePeer>,
+
+    /// Names of platform packages and binaries.
+    pub naming: NamingConfig,
+
+    /// If specified, `plugin package` generates a loader at this path,
+    /// relative to the crate directory.
+    ///
+    /// The loader exports the path of the binary for the current platform.
+    pub loader: Option<String>,
+
+    /// Include `sbom.cdx.json` in platform packages.
+    ///
+    /// The SBOM is always written to the package directory, but it's not
+    /// published unless this is `true`.
+    pub include_sbom: bool,
+
+    /// Platforms which must be packaged, like `linux-x64-gnu`.
+    ///
+    /// `plugin package --partial` fails if a binary for one of these is
+    /// missing.
+    pub required_platforms: Vec<String>,
+
+    /// SPDX license identifiers allowed for crates linked into plugins, like
+    /// `MIT`.
+    ///
+    /// If this is specified, `plugin package` fails when the license of a
+    /// dependency is not satisfied by this list.
+    pub allowed_licenses: Option<Vec<String>>,
+
+    /// Put binaries for all platforms into one package, instead of a package
+    /// for each platform.
+    ///
+    /// The package is named by `naming.fat-package` and the main package
+    /// depends on it.
+    pub fat_package: bool,
+
+    /// Package a universal binary for macOS (`darwin-universal`) instead of
+    /// `darwin-x64` and `darwin-arm64`.
+    pub universal_darwin: bool,
+
+    /// The npm registry to publish packages to.
+    pub registry: RegistryConfig,
+}
+
+/// Overrides `.npmrc` for publishing.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RegistryConfig {
+    /// URL of the registry, like `https://registry.npmjs.org/`.
+    pub url: Option<String>,
+
+    /// Name of the environment variable containing the token.
+    pub token_env: Option<String>,
+
+    /// Publish using the npm cli instead of the built-in client.
+    pub use_npm: bool,
+}
+
+/// Templates for names.
+///
+/// `{name}` (name of the main npm package), `{crate}`, `{platform}`
+/// (`linux-x64-gnu`), `{os}`, `{arch}` and `{abi}` are replaced.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct NamingConfig {
+    /// Name of platform packages.
+    pub package: String,
+
+    /// File name of the binary in platform packages, without the extension.
+    pub binary: String,
+
+    /// Name of the package containing binaries for all platforms, which is
+    /// used if `fat-package` is `true`. Only `{name}` and `{crate}` are
+    /// replaced.
+    pub fat_package: String,
+}
+
+impl Default for NamingConfig {
+    fn default() -> Self {
+        NamingConfig {
+            package: "{name}-{platform}".into(),
+            binary: "lib".into(),
+            fat_package: "{name}-all".into(),
+        }
+    }
+}
+
+impl NamingConfig {
+    /// Fails if names rendered from the templates would be invalid, like
+    /// platform packages with the same name.
+    pub fn validate(&self) -> Result<()> {
+        let has = |p: &str| self.package.contains(p);
+        let per_platform = has("{platform}") || (has("{os}") && has("{arch}") && has("{abi}"));
+        if !per_platform {
+            bail!(
+                "`naming.package` (`{}`) should contain `{{platform}}`, or `{{os}}`, `{{arch}}` \
+                 and `{{abi}}`, so that each platform has a different package",
+                self.package
+            )
+        }
+
+        if self.binary.is_empty() || self.binary.contains(&['/', '\\'][..]) {
+            bail!(
+                "`naming.binary` (`{}`) should be a file name without a path separator",
+                self.binary
+            )
+        }
+
+        Ok(())
+    }
+
+    /// Name of the npm package for `platform`.
+    pub fn package_name(&self, name: &str, crate_name: &str, platform: &PlatformDetail) -> String {
+        render(&self.package, name, crate_name, platform)
+    }
+
+    /// Name of the npm package for all platforms.
+    pub fn fat_package_name(&self, name: &str, crate_name: &str) -> String {
+        self.fat_package
+            .replace("{name}", name)
+            .replace("{crate}", crate_name)
+    }
+
+    /// File name of the binary for `platform`, including the extension.
+    pub fn binary_filename(
+        &self,
+        name: &str,
+        crate_name: &str,
+        platform: &PlatformDetail,
+    ) -> String {
+        format!(
+            "{}{}",
+            render(&self.binary, name, crate_name, platform),
+            platform.platform.cdylib_ext()
+        )
+    }
+}
+
+fn render(template: &str, name: &str, crate_name: &str, platform: &PlatformDetail) -> String {
+    template
+        .replace("{name}", name)
+        .replace("{crate}", crate_name)
+        .replace("{platform}", &platform.platform_arch_abi)
+        .replace("{os}", &platform.platform.to_string())
+        .replace("{arch}", &platform.arch.to_string())
+        .replace("{abi}", platform.abi.as_deref().unwrap_or_default())
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SwcCorePeer {
+    /// Version requirement for `swc_plugin`, like `0.10`.
+    pub swc_plugin: String,
+
+    /// Version range of `@swc/core`, like `>=1.2.100 <1.3.0`.
+    pub swc_core: String,
+}
+
+/// Controls which fields of the main `package.json` are copied to binary
+/// packages.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct PackageJsonConfig {
+    /// Fields to copy even if they are in the default denylist.
+    pub keep: Vec<String>,
+
+    /// Fields to not copy.
+    pub drop: Vec<String>,
+}
+
+#[cached(result)]
+pub fn load_config() -> Result<Config> {
+    (|| -> Result<_> {
+        let mut cmd = MetadataCommand::new();
+        cmd.no_deps();
+        let md = cargo_metadata(cmd, &env::current_dir()?)?;
+
+        let root_manifest = md.workspace_root.join("Cargo.toml");
+        let config = match md.workspace_metadata.get("swc-dev") {
+            Some(v) => v.clone(),
+            None => md
+                .packages
+                .iter()
+                .find(|p| p.manifest_path == root_manifest)
+                .and_then(|p| p.metadata.get("swc-dev").cloned())
+                .unwrap_or(Value::Null),
+        };
+
+        if config.is_null() {
+            return Ok(Config::default());
+        }
+
+        let config: Config = serde_json::from_value(config)?;
+        config.naming.validate()?;
+
+        Ok(config)
+    })()
+    .context("failed to load the configuration from `metadata.swc-dev` of `Cargo.toml`")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naming(package: &str, binary: &str) -> NamingConfig {
+        NamingConfig {
+            package: package.into(),
+            binary: binary.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn valid_naming() {
+        NamingConfig::default().validate().unwrap();
+        naming("@my-org-bin/{crate}-{platform}", "{crate}")
+            .validate()
+            .unwrap();
+        naming("{name}-{os}-{arch}-{abi}", "{crate}-{platform}")
+            .validate()
+            .unwrap();
+    }
+
+    #[test]
+    fn package_name_without_platform() {
+        for package in &["{name}", "{name}-{os}", "{name}-{os}-{arch}"] {
+            assert!(naming(package, "lib").validate().is_err(), "{}", package);
+        }
+    }
+
+    #[test]
+    fn binary_with_path() {
+        for binary in &["", "../lib", "bin/{crate}", "bin\\{crate}"] {
+            assert!(
+                naming("{name}-{platform}", binary).validate().is_err(),
+                "{}",
+                binary
+            );
+        }
+    }
+}
@@ -1,8 +1,18 @@
-use anyhow::{anyhow, Error};
+use anyhow::{anyhow, Context, Error};
 use once_cell::sync::Lazy;
-use std::{collections::HashMap, env, fmt::Display, path::PathBuf, sync::RwLock};
+use std::{
+    collections::HashMap,
+    env,
+    fmt::Display,
+    fs::{copy, hard_link, remove_file},
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+use tracing::debug;
 
+pub mod binary;
 pub mod cargo;
+pub mod config;
 pub mod node;
 
 pub type AHashMap<K, V> = HashMap<K, V, ahash::RandomState>;
@@ -48,3 +58,54 @@ pub(crate) fn find_executable(name: &str) -> Option<PathBuf> {
 
     path
 }
+
+/// Copies `from` to `to`, overwriting `to`.
+///
+/// If `link` is true, this creates a hard link instead and falls back to
+/// copying if it fails. (e.g. `from` and `to` are on different file systems)
+pub(crate) fn copy_or_link(from: &Path, to: &Path, link: bool) -> Result<(), Error> {
+    // `to` may be a hard link to `from` created by a previous run, and copying to
+    // it would truncate `from`.
+    if to.exists() {
+        remove_file(to).with_context(|| format!("failed to remove `{}`", to.display()))?;
+    }
+
+    if link {
+        match hard_link(from, to) {
+            Ok(()) => return Ok(()),
+            Err(err) => {
+                debug!(
+                    "failed to create a hard link from `{}` to `{}`; copying instead: {}",
+                    from.display(),
+                    to.display(),
+                    err
+                );
+            }
+        }
+    }
+
+    copy(from, to)
+        .with_context(|| format!("failed to copy `{}` to `{}`", from.display(), to.display()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{read, write};
+
+    #[test]
+    fn copy_over_hard_link() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("from.so");
+        let to = dir.path().join("to.so");
+        write(&from, b"binary").unwrap();
+
+        copy_or_link(&from, &to, true).unwrap();
+        copy_or_link(&from, &to, false).unwrap();
+
+        assert_eq!(read(&from).unwrap(), b"binary");
+        assert_eq!(read(&to).unwrap(), b"binary");
+    }
+}
@@ -1,54 +1,35 @@
 use crate::util::find_executable;
 use anyhow::{anyhow, bail, Context, Result};
-use std::{
-    path::{Path, PathBuf},
-    process::{Command, Stdio},
-};
+use serde_json::Value;
+use std::{path::Path, process::Command};
 use tracing::info;
 
+pub mod pack;
 pub mod platform;
+pub mod registry;
+#[cfg(test)]
+pub(crate) mod stub_registry;
+pub mod validate;
 
-/// Returned path is path to the built (and compressed) npm package file.
-pub fn create_npm_package(cwd: &Path) -> Result<PathBuf> {
+fn npm_command() -> Result<Command> {
     let npm_path =
         find_executable("npm").ok_or_else(|| anyhow!("failed to find `npm` from path"))?;
 
-    let mut cmd = if cfg!(target_os = "windows") {
+    Ok(if cfg!(target_os = "windows") {
         let mut c = Command::new("cmd");
         c.arg("/C").arg(&npm_path);
         c
     } else {
         Command::new(npm_path)
-    };
-
-    cmd.current_dir(&cwd);
-    cmd.arg("pack");
-
-    let output = cmd
-        .stderr(Stdio::inherit())
-        .output()
-        .context("failed to spawn `npm pack`")?;
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-
-    Ok(cwd.join(stdout.trim()))
+    })
 }
 
-pub fn publish_tarball_to_npm(path: &Path, access: Option<&str>) -> Result<()> {
+pub fn publish_tarball_to_npm(path: &Path, access: Option<&str>, tag: &str) -> Result<()> {
     info!("Publishing platform package at {}", path.display());
 
-    let npm_path =
-        find_executable("npm").ok_or_else(|| anyhow!("failed to find `npm` from path"))?;
-
-    let mut cmd = if cfg!(target_os = "windows") {
-        let mut c = Command::new("cmd");
-        c.arg("/C").arg(&npm_path);
-        c
-    } else {
-        Command::new(npm_path)
-    };
+    let mut cmd = npm_command()?;
 
-    cmd.arg("publish").arg(&path);
+    cmd.arg("publish").arg(&path).arg("--tag").arg(tag);
     if let Some(access) = access {
         cmd.arg("--access").arg(access);
     }
@@ -61,3 +42,48 @@ pub fn publish_tarball_to_npm(path: &Path, access: Option<&str>) -> Result<()> {
 
     Ok(())
 }
+
+/// Points `tag` of `name` to `version`, like `npm dist-tag add`.
+pub fn add_dist_tag_with_npm(name: &str, version: &str, tag: &str) -> Result<()> {
+    let status = npm_command()?
+        .arg("dist-tag")
+        .arg("add")
+        .arg(format!("{}@{}", name, version))
+        .arg(tag)
+        .status()
+        .context("failed to spawn `npm dist-tag add`")?;
+
+    if !status.success() {
+        bail!("`npm dist-tag add` for `{}@{}` failed", name, version)
+    }
+
+    Ok(())
+}
+
+/// Compares top-level fields of two JSON objects, like `package.json` files.
+///
+/// Changed fields are shown as a removed line and an added line.
+pub fn json_diff(old: &Value, new: &Value) -> Vec<String> {
+    let empty = Default::default();
+    let old = old.as_object().unwrap_or(&empty);
+    let new = new.as_object().unwrap_or(&empty);
+
+    let mut lines = vec![];
+    for (key, old_value) in old {
+        match new.get(key) {
+            Some(new_value) if new_value == old_value => {}
+            Some(new_value) => {
+                lines.push(format!("- {:?}: {}", key, old_value));
+                lines.push(format!("+ {:?}: {}", key, new_value));
+            }
+            None => lines.push(format!("- {:?}: {}", key, old_value)),
+        }
+    }
+    for (key, new_value) in new {
+        if !old.contains_key(key) {
+            lines.push(format!("+ {:?}: {}", key, new_value));
+        }
+    }
+
+    lines
+}
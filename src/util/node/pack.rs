@@ -0,0 +1,321 @@
+use anyhow::{bail, Context, Result};
+use flate2::{read::GzDecoder, Compression, GzBuilder};
+use serde::Deserialize;
+use serde_json::Value;
+use sha1::Sha1;
+use sha2::{Digest, Sha512};
+use std::{
+    fs::{read, read_dir, read_to_string, write, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+use tar::{Archive, EntryType, Header};
+use tracing::{debug, info};
+
+/// `1985-10-26T08:15:00.000Z`, which is used by npm.
+const NPM_MTIME: u64 = 499162500;
+
+/// Files which are included by npm regardless of `files` in `package.json`.
+const ALWAYS_INCLUDED: &[&str] = &["readme", "license", "licence", "copying", "changelog"];
+
+/// A npm package file created by [create_npm_package].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NpmTarball {
+    pub path: PathBuf,
+    pub name: String,
+    pub version: String,
+    /// Subresource integrity string, like `sha512-...`.
+    pub integrity: String,
+    /// Hex-encoded sha1, which is used as `shasum` by npm.
+    pub shasum: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PackageJson {
+    name: String,
+    version: String,
+    #[serde(default)]
+    files: Option<Vec<String>>,
+}
+
+/// Creates a npm package file from the package directory `pkg_dir`, like `npm
+/// pack`.
+///
+/// The created file is stored next to `pkg_dir` and it's reproducible. Entries
+/// are sorted and all metadata of entries are normalized.
+pub fn create_npm_package(pkg_dir: &Path) -> Result<NpmTarball> {
+    let package_json_path = pkg_dir.join("package.json");
+    let package_json_str = read_to_string(&package_json_path)
+        .with_context(|| format!("failed to read `{}`", package_json_path.display()))?;
+    let package_json: PackageJson = serde_json::from_str(&package_json_str)
+        .with_context(|| format!("failed to parse `{}`", package_json_path.display()))?;
+
+    let mut files = vec![];
+    collect_files(pkg_dir, "", &mut files)
+        .with_context(|| format!("failed to read `{}`", pkg_dir.display()))?;
+    let files = packed_files(package_json.files.as_deref(), files);
+
+    let bytes = pack_files(pkg_dir, &files).context("failed to create a tarball")?;
+
+    let filename = format!(
+        "{}-{}.tgz",
+        package_json.name.trim_start_matches('@').replace('/', "-"),
+        package_json.version
+    );
+    let path = match pkg_dir.parent() {
+        Some(dir) => dir.join(filename),
+        None => bail!("package directory should have a parent directory"),
+    };
+
+    write(&path, &bytes).with_context(|| format!("failed to write `{}`", path.display()))?;
+
+    let integrity = integrity_of(&bytes);
+    let shasum = hex::encode(Sha1::digest(&bytes));
+
+    info!(
+        integrity = &*integrity,
+        "Created npm package at {}",
+        path.display()
+    );
+
+    Ok(NpmTarball {
+        path,
+        name: package_json.name,
+        version: package_json.version,
+        integrity,
+        shasum,
+    })
+}
+
+/// Subresource integrity string of a npm package file, like `sha512-...`.
+pub fn integrity_of(bytes: &[u8]) -> String {
+    format!("sha512-{}", base64::encode(Sha512::digest(bytes)))
+}
+
+/// Reads `package.json` in a npm package file.
+pub fn read_package_json_in_tarball(path: &Path) -> Result<Value> {
+    (|| -> Result<_> {
+        let mut archive = Archive::new(GzDecoder::new(File::open(path)?));
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if &*entry.path()? != Path::new("package/package.json") {
+                continue;
+            }
+
+            let mut s = String::new();
+            entry.read_to_string(&mut s)?;
+            return Ok(serde_json::from_str(&s)?);
+        }
+
+        bail!("the package does not contain `package/package.json`")
+    })()
+    .with_context(|| format!("failed to read package.json in `{}`", path.display()))
+}
+
+/// Collects path of files in `dir`, relative to the package directory and
+/// separated by `/`.
+fn collect_files(dir: &Path, prefix: &str, buf: &mut Vec<String>) -> Result<()> {
+    for e in read_dir(dir)? {
+        let e = e?;
+        let name = e.file_name().to_string_lossy().to_string();
+        let rel_path = format!("{}{}", prefix, name);
+        let ty = e.file_type()?;
+
+        if ty.is_dir() {
+            if name == "node_modules" || name == ".git" {
+                continue;
+            }
+
+            collect_files(&e.path(), &format!("{}/", rel_path), buf)?;
+        } else if ty.is_file() {
+            buf.push(rel_path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the files in `candidates` which are included in the package, sorted.
+///
+/// `files` is the field of `package.json`.
+pub fn packed_files(files: Option<&[String]>, mut candidates: Vec<String>) -> Vec<String> {
+    candidates.retain(|f| should_include(files, f));
+    candidates.sort();
+    candidates
+}
+
+fn should_include(files: Option<&[String]>, rel_path: &str) -> bool {
+    if rel_path.ends_with(".tgz") {
+        return false;
+    }
+
+    if rel_path == "package.json" {
+        return true;
+    }
+
+    if !rel_path.contains('/') {
+        let stem = rel_path.split('.').next().unwrap().to_ascii_lowercase();
+        if ALWAYS_INCLUDED.contains(&&*stem) {
+            return true;
+        }
+    }
+
+    match files {
+        Some(files) => files.iter().any(|f| {
+            let f = f.trim_start_matches("./").trim_end_matches('/');
+            rel_path == f || rel_path.starts_with(&format!("{}/", f))
+        }),
+        None => true,
+    }
+}
+
+fn pack_files(pkg_dir: &Path, files: &[String]) -> Result<Vec<u8>> {
+    let encoder = GzBuilder::new()
+        .mtime(0)
+        .write(Vec::new(), Compression::best());
+    let mut builder = tar::Builder::new(encoder);
+
+    for rel_path in files {
+        debug!("Adding `{}`", rel_path);
+
+        let data = read(pkg_dir.join(rel_path))
+            .with_context(|| format!("failed to read `{}`", rel_path))?;
+
+        let mut header = Header::new_ustar();
+        header.set_entry_type(EntryType::Regular);
+        header.set_mode(0o644);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_mtime(NPM_MTIME);
+        header.set_size(data.len() as u64);
+
+        builder
+            .append_data(&mut header, format!("package/{}", rel_path), &*data)
+            .with_context(|| format!("failed to add `{}`", rel_path))?;
+    }
+
+    let encoder = builder.into_inner()?;
+    Ok(encoder.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::create_dir_all;
+    use tempfile::TempDir;
+
+    fn create_package_dir(dir: &Path) -> PathBuf {
+        let pkg_dir = dir.join("pkg");
+        create_dir_all(pkg_dir.join("bin/linux-x64-gnu")).unwrap();
+        write(
+            pkg_dir.join("package.json"),
+            r#"{ "name": "@swc/plugin", "version": "1.0.0", "files": ["bin"] }"#,
+        )
+        .unwrap();
+        write(pkg_dir.join("README.md"), "# plugin").unwrap();
+        write(pkg_dir.join("bin/linux-x64-gnu/lib.so"), "binary").unwrap();
+        write(pkg_dir.join("index.js"), "").unwrap();
+        pkg_dir
+    }
+
+    fn list_entries(path: &Path) -> Vec<Header> {
+        let mut archive = Archive::new(GzDecoder::new(File::open(path).unwrap()));
+        archive
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap().header().clone())
+            .collect()
+    }
+
+    #[test]
+    fn reproducible() {
+        let dir = TempDir::new().unwrap();
+        let pkg_dir = create_package_dir(dir.path());
+
+        let first = create_npm_package(&pkg_dir).unwrap();
+        let first_bytes = read(&first.path).unwrap();
+
+        // Rewriting files changes their mtime, which should not be packed.
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        create_package_dir(dir.path());
+
+        let second = create_npm_package(&pkg_dir).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(first_bytes, read(&second.path).unwrap());
+        assert_eq!(first.integrity, integrity_of(&first_bytes));
+        assert_eq!(first.shasum, hex::encode(Sha1::digest(&first_bytes)));
+        assert_eq!(first.path, dir.path().join("swc-plugin-1.0.0.tgz"));
+    }
+
+    #[test]
+    fn normalized_entries() {
+        let dir = TempDir::new().unwrap();
+        let tarball = create_npm_package(&create_package_dir(dir.path())).unwrap();
+
+        let entries = list_entries(&tarball.path);
+        let paths = entries
+            .iter()
+            .map(|h| h.path().unwrap().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                "package/README.md",
+                "package/bin/linux-x64-gnu/lib.so",
+                "package/package.json",
+            ]
+        );
+
+        for header in &entries {
+            assert_eq!(header.mode().unwrap(), 0o644);
+            assert_eq!(header.mtime().unwrap(), NPM_MTIME);
+            assert_eq!(header.uid().unwrap(), 0);
+            assert_eq!(header.gid().unwrap(), 0);
+        }
+    }
+
+    #[test]
+    fn files_field() {
+        let candidates = [
+            "CHANGELOG.md",
+            "LICENSE",
+            "README.md",
+            "bin/lib.so",
+            "binary.js",
+            "docs/README.md",
+            "index.js",
+            "lib/index.js",
+            "package.json",
+            "plugin-1.0.0.tgz",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+
+        let files = vec!["./bin/".to_string(), "index.js".to_string()];
+        assert_eq!(
+            packed_files(Some(&files), candidates.clone()),
+            vec![
+                "CHANGELOG.md",
+                "LICENSE",
+                "README.md",
+                "bin/lib.so",
+                "index.js",
+                "package.json",
+            ]
+        );
+
+        assert_eq!(
+            packed_files(Some(&[]), candidates.clone()),
+            vec!["CHANGELOG.md", "LICENSE", "README.md", "package.json"]
+        );
+
+        let mut all = candidates;
+        all.pop();
+        assert_eq!(
+            packed_files(None, all.clone().into_iter().rev().collect()),
+            all
+        );
+    }
+}
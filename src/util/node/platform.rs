@@ -3,6 +3,9 @@ use swc_node_arch::{NodeArch, NodePlatform, PlatformDetail};
 
 pub fn parse_node_platform(s: &str) -> Result<PlatformDetail, Error> {
     let ss = s.split('-').collect::<Vec<_>>();
+    if ss.len() < 2 {
+        bail!("Platform must be one of {:?}", possible_strings())
+    }
 
     let platform = NodePlatform::from_sys(&ss[0])?;
     let arch = NodeArch::from_cpu(&ss[1])?;
@@ -33,6 +36,8 @@ fn parse(s: &str) -> PlatformDetail {
     parse_node_platform(s).unwrap()
 }
 
+/// Platforms packaged by default. `darwin-universal` is not included because
+/// it replaces `darwin-x64` and `darwin-arm64`.
 fn possible_strings() -> Vec<&'static str> {
     vec![
         "win32-x64-msvc",
@@ -50,6 +55,121 @@ fn possible_strings() -> Vec<&'static str> {
     ]
 }
 
+/// Get the rust target triple for a platform.
+pub fn rust_target_of(p: &PlatformDetail) -> Option<String> {
+    // Parsed from a target triple.
+    if p.raw != p.platform_arch_abi {
+        return Some(p.raw.clone());
+    }
+
+    let target = match &*p.platform_arch_abi {
+        "win32-x64-msvc" => "x86_64-pc-windows-msvc",
+        "darwin-x64" => "x86_64-apple-darwin",
+        "linux-x64-gnu" => "x86_64-unknown-linux-gnu",
+        "linux-x64-musl" => "x86_64-unknown-linux-musl",
+        "win32-ia32-msvc" => "i686-pc-windows-msvc",
+        "linux-arm64-gnu" => "aarch64-unknown-linux-gnu",
+        "linux-arm-gnueabihf" => "armv7-unknown-linux-gnueabihf",
+        "darwin-arm64" => "aarch64-apple-darwin",
+        "android-arm64" => "aarch64-linux-android",
+        "freebsd-x64" => "x86_64-unknown-freebsd",
+        "linux-arm64-musl" => "aarch64-unknown-linux-musl",
+        "win32-arm64-msvc" => "aarch64-pc-windows-msvc",
+        _ => return None,
+    };
+
+    Some(target.to_string())
+}
+
+/// Get the rust target triples of the binary for a platform.
+///
+/// An universal binary of macOS is built for two targets.
+pub fn rust_targets_of(p: &PlatformDetail) -> Vec<String> {
+    if p.arch == NodeArch::Universal {
+        return vec!["x86_64-apple-darwin".into(), "aarch64-apple-darwin".into()];
+    }
+
+    rust_target_of(p).into_iter().collect()
+}
+
+/// All supported platforms, including `darwin-universal`.
 pub fn all_node_platforms() -> Vec<PlatformDetail> {
-    possible_strings().into_iter().map(parse).collect()
+    let mut platforms = possible_strings()
+        .into_iter()
+        .map(parse)
+        .collect::<Vec<_>>();
+    platforms.push(parse("darwin-universal"));
+    platforms
+}
+
+/// Platforms packaged if `--platforms` is not specified.
+///
+/// If `universal_darwin` is `true`, `darwin-universal` is used instead of
+/// `darwin-x64` and `darwin-arm64`.
+pub fn default_node_platforms(universal_darwin: bool) -> Vec<PlatformDetail> {
+    let mut platforms = possible_strings()
+        .into_iter()
+        .map(parse)
+        .collect::<Vec<_>>();
+    if universal_darwin {
+        platforms.push(parse("darwin-universal"));
+        remove_thin_darwin(&mut platforms);
+    }
+    platforms
+}
+
+/// Removes `darwin-x64` and `darwin-arm64` if `darwin-universal` is in
+/// `platforms`, so that macOS users install only the universal binary.
+///
+/// Returns the removed platforms.
+pub fn remove_thin_darwin(platforms: &mut Vec<PlatformDetail>) -> Vec<PlatformDetail> {
+    if !platforms.iter().any(|p| p.arch == NodeArch::Universal) {
+        return vec![];
+    }
+
+    let (thin, rest) = platforms
+        .drain(..)
+        .partition(|p| p.platform == NodePlatform::Darwin && p.arch != NodeArch::Universal);
+    *platforms = rest;
+    thin
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(platforms: &[PlatformDetail]) -> Vec<String> {
+        platforms.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn universal_is_opt_in() {
+        let platforms = names(&default_node_platforms(false));
+        assert!(platforms.contains(&"darwin-x64".to_string()));
+        assert!(platforms.contains(&"darwin-arm64".to_string()));
+        assert!(!platforms.contains(&"darwin-universal".to_string()));
+
+        let platforms = names(&default_node_platforms(true));
+        assert!(!platforms.contains(&"darwin-x64".to_string()));
+        assert!(!platforms.contains(&"darwin-arm64".to_string()));
+        assert!(platforms.contains(&"darwin-universal".to_string()));
+    }
+
+    #[test]
+    fn thin_darwin_is_removed_only_with_universal() {
+        let mut platforms = vec![parse("darwin-x64"), parse("linux-x64-gnu")];
+        assert!(remove_thin_darwin(&mut platforms).is_empty());
+        assert_eq!(names(&platforms), vec!["darwin-x64", "linux-x64-gnu"]);
+
+        let mut platforms = vec![
+            parse("darwin-x64"),
+            parse("darwin-universal"),
+            parse("darwin-arm64"),
+        ];
+        assert_eq!(
+            names(&remove_thin_darwin(&mut platforms)),
+            vec!["darwin-x64", "darwin-arm64"]
+        );
+        assert_eq!(names(&platforms), vec!["darwin-universal"]);
+    }
 }
@@ -0,0 +1,569 @@
+use super::pack::{integrity_of, read_package_json_in_tarball};
+use crate::util::{cargo::cargo_workspace_root, config::RegistryConfig};
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha1::Sha1;
+use sha2::Digest;
+use std::{
+    env,
+    fs::{read, read_to_string},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::{debug, info};
+
+pub const DEFAULT_REGISTRY: &str = "https://registry.npmjs.org/";
+
+/// The dist-tag for `version` if it's not specified.
+///
+/// Prereleases are not tagged as `latest`. `1.0.0-canary.1` is tagged as
+/// `canary`, and other prereleases like `1.0.0-beta.1` are tagged as `next`.
+pub fn default_dist_tag(version: &str) -> &'static str {
+    let version = version.split('+').next().unwrap_or_default();
+    match version.split_once('-') {
+        Some((_, pre)) if pre.starts_with("canary") => "canary",
+        Some(_) => "next",
+        None => "latest",
+    }
+}
+
+/// npm rejects tags which can be parsed as a version range.
+pub fn validate_dist_tag(tag: &str) -> Result<()> {
+    let looks_like_version = tag
+        .trim_start_matches('v')
+        .starts_with(|c: char| c.is_ascii_digit());
+    if tag.is_empty() || looks_like_version || tag.contains(|c: char| c.is_whitespace()) {
+        bail!("`{}` is not a valid dist-tag", tag)
+    }
+
+    Ok(())
+}
+
+/// Settings read from `.npmrc` files.
+///
+/// Values in files read earlier take precedence, like npm.
+#[derive(Debug, Default, Clone)]
+pub struct Npmrc {
+    entries: Vec<(String, String)>,
+}
+
+impl Npmrc {
+    /// Reads `.npmrc` in the current directory, the workspace root and the
+    /// home directory.
+    pub fn load() -> Result<Self> {
+        let mut paths = vec![];
+        if let Ok(dir) = env::current_dir() {
+            paths.push(dir.join(".npmrc"));
+        }
+        if let Ok(dir) = cargo_workspace_root() {
+            paths.push(dir.join(".npmrc"));
+        }
+        if let Some(home) = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")) {
+            paths.push(PathBuf::from(home).join(".npmrc"));
+        }
+        paths.dedup();
+
+        let mut npmrc = Npmrc::default();
+        for path in paths {
+            if !path.is_file() {
+                continue;
+            }
+
+            debug!("Reading {}", path.display());
+            let s = read_to_string(&path)
+                .with_context(|| format!("failed to read `{}`", path.display()))?;
+            npmrc.entries.extend(parse_npmrc(&s));
+        }
+
+        Ok(npmrc)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| &**v)
+    }
+}
+
+/// Parses `key=value` lines and expands `${ENV}` in values.
+fn parse_npmrc(s: &str) -> Vec<(String, String)> {
+    s.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with(';'))
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            let value = value.trim().trim_matches('"');
+            Some((key.trim().to_string(), expand_env(value)))
+        })
+        .collect()
+}
+
+fn expand_env(s: &str) -> String {
+    let mut buf = String::new();
+    let mut rest = s;
+
+    while let Some(start) = rest.find("${") {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+
+        buf.push_str(&rest[..start]);
+        buf.push_str(&env::var(&rest[start + 2..end]).unwrap_or_default());
+        rest = &rest[end + 1..];
+    }
+    buf.push_str(rest);
+
+    buf
+}
+
+/// Result of [NpmRegistry::publish].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Published {
+    pub name: String,
+    pub version: String,
+    pub integrity: String,
+}
+
+/// `dist` of a published version.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Dist {
+    pub integrity: Option<String>,
+    pub shasum: Option<String>,
+}
+
+impl Dist {
+    /// Checks if the published package file is same as `bytes`.
+    ///
+    /// `integrity` is compared if the registry provides it, because old
+    /// versions only have `shasum`.
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        match (&self.integrity, &self.shasum) {
+            (Some(integrity), _) => *integrity == integrity_of(bytes),
+            (None, Some(shasum)) => *shasum == hex::encode(Sha1::digest(bytes)),
+            (None, None) => false,
+        }
+    }
+}
+
+/// A client for the HTTP API of a npm registry.
+#[derive(Debug, Clone)]
+pub struct NpmRegistry {
+    /// Ends with `/`.
+    url: String,
+    token: Option<String>,
+    agent: ureq::Agent,
+}
+
+impl NpmRegistry {
+    pub fn new(url: &str, token: Option<String>) -> Self {
+        let url = if url.ends_with('/') {
+            url.to_string()
+        } else {
+            format!("{}/", url)
+        };
+
+        NpmRegistry {
+            url,
+            token,
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(300))
+                .build(),
+        }
+    }
+
+    /// Selects the registry for `package_name` and its token.
+    ///
+    /// `registry` of the configuration takes precedence over `.npmrc`.
+    pub fn for_package(package_name: &str, config: &RegistryConfig, npmrc: &Npmrc) -> Self {
+        let scope = package_name
+            .strip_prefix('@')
+            .and_then(|s| s.split_once('/'))
+            .map(|(scope, _)| format!("@{}:registry", scope));
+
+        let url = config
+            .url
+            .clone()
+            .or_else(|| scope.and_then(|key| npmrc.get(&key).map(String::from)))
+            .or_else(|| npmrc.get("registry").map(String::from))
+            .unwrap_or_else(|| DEFAULT_REGISTRY.to_string());
+
+        let token = config
+            .token_env
+            .as_ref()
+            .and_then(|name| env::var(name).ok())
+            .or_else(|| token_from_npmrc(&url, npmrc));
+
+        NpmRegistry::new(&url, token)
+    }
+
+    /// Publishes a npm package file, like `npm publish <tarball>`.
+    pub fn publish(&self, tarball: &Path, access: Option<&str>, tag: &str) -> Result<Published> {
+        let bytes =
+            read(tarball).with_context(|| format!("failed to read `{}`", tarball.display()))?;
+        let mut manifest = read_package_json_in_tarball(tarball)?;
+
+        let name = manifest["name"]
+            .as_str()
+            .context("package.json does not have `name`")?
+            .to_string();
+        let version = manifest["version"]
+            .as_str()
+            .context("package.json does not have `version`")?
+            .to_string();
+        let integrity = integrity_of(&bytes);
+
+        let unscoped_name = name.rsplit('/').next().unwrap();
+        let tarball_url = format!("{}{}/-/{}-{}.tgz", self.url, name, unscoped_name, version);
+
+        manifest["_id"] = json!(format!("{}@{}", name, version));
+        manifest["dist"] = json!({
+            "integrity": integrity,
+            "shasum": hex::encode(Sha1::digest(&bytes)),
+            "tarball": tarball_url,
+        });
+
+        let doc = json!({
+            "_id": name,
+            "name": name,
+            "description": manifest.get("description").cloned().unwrap_or(Value::Null),
+            "dist-tags": { tag: version },
+            "versions": { version.clone(): manifest },
+            "access": access,
+            "_attachments": {
+                format!("{}-{}.tgz", name, version): {
+                    "content_type": "application/octet-stream",
+                    "data": base64::encode(&bytes),
+                    "length": bytes.len(),
+                }
+            },
+        });
+
+        info!(
+            "Publishing {}@{} to {} with tag `{}`",
+            name, version, self.url, tag
+        );
+
+        let req = self
+            .request("PUT", &self.package_url(&name))
+            .set("npm-command", "publish");
+        match req.send_json(doc) {
+            Ok(_) => {}
+            Err(ureq::Error::Status(status, resp)) => {
+                let body = resp.into_string().unwrap_or_default();
+                bail!(
+                    "failed to publish {}@{} ({}): {}",
+                    name,
+                    version,
+                    status,
+                    error_message(&body)
+                )
+            }
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to connect to {}", self.url))
+            }
+        }
+
+        Ok(Published {
+            name,
+            version,
+            integrity,
+        })
+    }
+
+    /// Points `tag` of `name` to `version`, like `npm dist-tag add`.
+    pub fn add_dist_tag(&self, name: &str, version: &str, tag: &str) -> Result<()> {
+        let url = format!(
+            "{}-/package/{}/dist-tags/{}",
+            self.url,
+            name.replace('/', "%2f"),
+            tag
+        );
+
+        match self.request("PUT", &url).send_json(json!(version)) {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(status, resp)) => {
+                let body = resp.into_string().unwrap_or_default();
+                bail!(
+                    "failed to add tag `{}` to {}@{} ({}): {}",
+                    tag,
+                    name,
+                    version,
+                    status,
+                    error_message(&body)
+                )
+            }
+            Err(err) => Err(err).with_context(|| format!("failed to connect to {}", self.url)),
+        }
+    }
+
+    /// Returns `dist` of `name@version`, or `None` if it's not published.
+    pub fn published_dist(&self, name: &str, version: &str) -> Result<Option<Dist>> {
+        debug!("Checking if {}@{} exists in {}", name, version, self.url);
+
+        let resp = match self
+            .request("GET", &self.package_url(name))
+            .set("accept", "application/json")
+            .call()
+        {
+            Ok(resp) => resp,
+            Err(ureq::Error::Status(404, _)) => return Ok(None),
+            Err(ureq::Error::Status(status, resp)) => {
+                let body = resp.into_string().unwrap_or_default();
+                bail!(
+                    "failed to get {} ({}): {}",
+                    name,
+                    status,
+                    error_message(&body)
+                )
+            }
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to connect to {}", self.url))
+            }
+        };
+
+        let packument: Value = resp
+            .into_json()
+            .with_context(|| format!("failed to parse the response for {}", name))?;
+
+        match packument["versions"].get(version) {
+            Some(v) => {
+                let dist = serde_json::from_value(v["dist"].clone())
+                    .with_context(|| format!("failed to parse `dist` of {}@{}", name, version))?;
+                Ok(Some(dist))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// URL of the packument, with `/` of a scoped name escaped.
+    fn package_url(&self, name: &str) -> String {
+        format!("{}{}", self.url, name.replace('/', "%2f"))
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        let req = self.agent.request(method, url);
+        match &self.token {
+            Some(token) => req.set("authorization", &format!("Bearer {}", token)),
+            None => req,
+        }
+    }
+}
+
+/// Finds `//host/path/:_authToken` for `url`, using the longest match.
+fn token_from_npmrc(url: &str, npmrc: &Npmrc) -> Option<String> {
+    let (_, without_scheme) = url.split_once("//")?;
+    let mut path = format!("//{}", without_scheme.trim_end_matches('/'));
+
+    loop {
+        if let Some(token) = npmrc.get(&format!("{}/:_authToken", path)) {
+            return Some(token.to_string());
+        }
+
+        match path.rfind('/') {
+            Some(i) if i > 1 => path.truncate(i),
+            _ => return None,
+        }
+    }
+}
+
+/// Extracts `error` from a json response of the registry.
+fn error_message(body: &str) -> String {
+    serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|v| {
+            v.get("error")
+                .or_else(|| v.get("message"))
+                .and_then(|v| v.as_str())
+                .map(String::from)
+        })
+        .unwrap_or_else(|| body.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::node::stub_registry::{create_tarball, StubRegistry};
+
+    fn npmrc(s: &str) -> Npmrc {
+        Npmrc {
+            entries: parse_npmrc(s),
+        }
+    }
+
+    #[test]
+    fn publish_document() {
+        let dir = tempfile::tempdir().unwrap();
+        let tarball = create_tarball(dir.path(), "@scope/plugin-linux-x64-gnu", "1.0.0", "");
+        let stub = StubRegistry::start();
+        let registry = NpmRegistry::new(stub.url(), Some("secret".into()));
+
+        let published = registry
+            .publish(&tarball.path, Some("public"), "next")
+            .unwrap();
+        assert_eq!(
+            published,
+            Published {
+                name: "@scope/plugin-linux-x64-gnu".into(),
+                version: "1.0.0".into(),
+                integrity: tarball.integrity.clone(),
+            }
+        );
+
+        let requests = stub.requests();
+        assert_eq!(requests.len(), 1);
+        let req = &requests[0];
+        assert_eq!(req.method, "PUT");
+        assert_eq!(req.path, "/@scope%2fplugin-linux-x64-gnu");
+        assert_eq!(req.authorization.as_deref(), Some("Bearer secret"));
+        assert_eq!(req.npm_command.as_deref(), Some("publish"));
+
+        let doc = req.body.as_ref().unwrap();
+        assert_eq!(doc["_id"], "@scope/plugin-linux-x64-gnu");
+        assert_eq!(doc["name"], "@scope/plugin-linux-x64-gnu");
+        assert_eq!(doc["access"], "public");
+        assert_eq!(doc["dist-tags"], json!({ "next": "1.0.0" }));
+
+        let manifest = &doc["versions"]["1.0.0"];
+        assert_eq!(manifest["_id"], "@scope/plugin-linux-x64-gnu@1.0.0");
+        assert_eq!(manifest["description"], "A test package");
+        assert_eq!(
+            manifest["dist"],
+            json!({
+                "integrity": tarball.integrity,
+                "shasum": tarball.shasum,
+                "tarball": format!(
+                    "{}@scope/plugin-linux-x64-gnu/-/plugin-linux-x64-gnu-1.0.0.tgz",
+                    stub.url()
+                ),
+            })
+        );
+
+        let attachments = doc["_attachments"].as_object().unwrap();
+        assert_eq!(attachments.len(), 1);
+        let attachment = &attachments["@scope/plugin-linux-x64-gnu-1.0.0.tgz"];
+        let bytes = read(&tarball.path).unwrap();
+        assert_eq!(attachment["content_type"], "application/octet-stream");
+        assert_eq!(attachment["length"], bytes.len());
+        assert_eq!(
+            base64::decode(attachment["data"].as_str().unwrap()).unwrap(),
+            bytes
+        );
+
+        let packument = stub.packument("@scope/plugin-linux-x64-gnu").unwrap();
+        assert_eq!(packument["dist-tags"]["next"], "1.0.0");
+    }
+
+    #[test]
+    fn parse_npmrc_files() {
+        env::set_var("SWC_DEV_TEST_NPMRC_TOKEN", "from-env");
+
+        let rc = npmrc(
+            "; comment\n# comment\n\nregistry = \"https://registry.example.com/\"\n\
+             //registry.example.com/:_authToken=${SWC_DEV_TEST_NPMRC_TOKEN}\n\
+             //registry.example.com/:_authToken=ignored\n",
+        );
+        assert_eq!(rc.get("registry"), Some("https://registry.example.com/"));
+        // The first value is used.
+        assert_eq!(
+            rc.get("//registry.example.com/:_authToken"),
+            Some("from-env")
+        );
+        assert_eq!(rc.get("; comment"), None);
+
+        assert_eq!(expand_env("${SWC_DEV_TEST_NPMRC_TOKEN}-x"), "from-env-x");
+        assert_eq!(expand_env("${SWC_DEV_TEST_NPMRC_MISSING}"), "");
+        assert_eq!(expand_env("${unclosed"), "${unclosed");
+    }
+
+    #[test]
+    fn token_lookup() {
+        env::set_var("SWC_DEV_TEST_LOOKUP_TOKEN", "scoped");
+
+        let rc = npmrc(
+            "//registry.example.com/:_authToken=root\n\
+             //registry.example.com/npm/private/:_authToken=${SWC_DEV_TEST_LOOKUP_TOKEN}\n\
+             @scope:registry=https://registry.example.com/npm/private/\n",
+        );
+
+        assert_eq!(
+            token_from_npmrc("https://registry.example.com/npm/private/", &rc).as_deref(),
+            Some("scoped")
+        );
+        assert_eq!(
+            token_from_npmrc("https://registry.example.com/npm/public", &rc).as_deref(),
+            Some("root")
+        );
+        assert_eq!(token_from_npmrc("https://other.example.com/", &rc), None);
+
+        let config = RegistryConfig::default();
+        let scoped = NpmRegistry::for_package("@scope/plugin", &config, &rc);
+        assert_eq!(scoped.url, "https://registry.example.com/npm/private/");
+        assert_eq!(scoped.token.as_deref(), Some("scoped"));
+
+        let unscoped = NpmRegistry::for_package("plugin", &config, &rc);
+        assert_eq!(unscoped.url, DEFAULT_REGISTRY);
+        assert_eq!(unscoped.token, None);
+
+        env::set_var("SWC_DEV_TEST_CONFIG_TOKEN", "from-config");
+        let config = RegistryConfig {
+            url: Some("https://registry.example.com".into()),
+            token_env: Some("SWC_DEV_TEST_CONFIG_TOKEN".into()),
+            use_npm: false,
+        };
+        let overridden = NpmRegistry::for_package("@scope/plugin", &config, &rc);
+        assert_eq!(overridden.url, "https://registry.example.com/");
+        assert_eq!(overridden.token.as_deref(), Some("from-config"));
+    }
+
+    #[test]
+    fn dist_matches() {
+        let bytes = b"package";
+        let integrity = Dist {
+            integrity: Some(integrity_of(bytes)),
+            shasum: Some("ignored".into()),
+        };
+        assert!(integrity.matches(bytes));
+        assert!(!integrity.matches(b"other"));
+
+        // Old versions only have `shasum`.
+        let shasum = Dist {
+            integrity: None,
+            shasum: Some(hex::encode(Sha1::digest(bytes))),
+        };
+        assert!(shasum.matches(bytes));
+        assert!(!shasum.matches(b"other"));
+
+        assert!(!Dist::default().matches(bytes));
+    }
+
+    #[test]
+    fn error_body() {
+        assert_eq!(error_message(r#"{"error":"Not found"}"#), "Not found");
+        assert_eq!(error_message(r#"{"message":"Forbidden"}"#), "Forbidden");
+        assert_eq!(error_message(" Bad Gateway\n"), "Bad Gateway");
+
+        let dir = tempfile::tempdir().unwrap();
+        let tarball = create_tarball(dir.path(), "plugin", "1.0.0", "");
+        let stub = StubRegistry::start();
+
+        let err = NpmRegistry::new(stub.url(), None)
+            .publish(&tarball.path, None, "latest")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "failed to publish plugin@1.0.0 (401): You must be logged in to publish packages."
+        );
+
+        let registry = NpmRegistry::new(stub.url(), Some("secret".into()));
+        registry.publish(&tarball.path, None, "latest").unwrap();
+        let err = registry.publish(&tarball.path, None, "latest").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "failed to publish plugin@1.0.0 (403): You cannot publish over the previously \
+             published versions: 1.0.0."
+        );
+    }
+}
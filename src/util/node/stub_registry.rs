@@ -0,0 +1,222 @@
+//! An in-process npm registry for tests.
+
+use super::pack::{create_npm_package, NpmTarball};
+use crate::util::AHashMap;
+use serde_json::{json, Value};
+use std::{
+    fs::{create_dir_all, write},
+    path::Path,
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+use tiny_http::{Method, Request, Response, Server};
+
+/// A request received by [StubRegistry].
+#[derive(Debug, Clone)]
+pub(crate) struct ReceivedRequest {
+    pub method: String,
+    /// Raw path, without decoding `%2f`.
+    pub path: String,
+    pub authorization: Option<String>,
+    pub npm_command: Option<String>,
+    pub body: Option<Value>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Packuments by package name.
+    packages: AHashMap<String, Value>,
+    requests: Vec<ReceivedRequest>,
+}
+
+/// Serves packuments, publishes and dist-tags like the npm registry, storing
+/// everything in memory.
+///
+/// Publishing requires a token, and publishing over an existing version fails.
+pub(crate) struct StubRegistry {
+    server: Arc<Server>,
+    state: Arc<Mutex<State>>,
+    handle: Option<JoinHandle<()>>,
+    url: String,
+}
+
+impl StubRegistry {
+    pub fn start() -> Self {
+        let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+        let url = format!("http://{}/", server.server_addr());
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let handle = {
+            let server = server.clone();
+            let state = state.clone();
+            thread::spawn(move || {
+                for mut req in server.incoming_requests() {
+                    let authorization = header(&req, "authorization");
+                    let npm_command = header(&req, "npm-command");
+
+                    let mut body = String::new();
+                    req.as_reader().read_to_string(&mut body).unwrap();
+                    let body = serde_json::from_str(&body).ok();
+
+                    let received = ReceivedRequest {
+                        method: req.method().to_string(),
+                        path: req.url().to_string(),
+                        authorization,
+                        npm_command,
+                        body,
+                    };
+
+                    let (status, res) = state.lock().unwrap().handle(req.method(), &received);
+                    state.lock().unwrap().requests.push(received);
+
+                    req.respond(Response::from_string(res.to_string()).with_status_code(status))
+                        .unwrap();
+                }
+            })
+        };
+
+        StubRegistry {
+            server,
+            state,
+            handle: Some(handle),
+            url,
+        }
+    }
+
+    /// Ends with `/`.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn requests(&self) -> Vec<ReceivedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Packument of `name`, or `None` if nothing is published.
+    pub fn packument(&self, name: &str) -> Option<Value> {
+        self.state.lock().unwrap().packages.get(name).cloned()
+    }
+
+    /// Adds a version with `dist` as if it was published before.
+    pub fn add_version(&self, name: &str, version: &str, dist: Value) {
+        let mut state = self.state.lock().unwrap();
+        let packument = state
+            .packages
+            .entry(name.to_string())
+            .or_insert_with(|| json!({ "name": name, "dist-tags": {}, "versions": {} }));
+
+        packument["versions"][version] = json!({ "name": name, "version": version, "dist": dist });
+        packument["dist-tags"]["latest"] = json!(version);
+    }
+}
+
+impl Drop for StubRegistry {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl State {
+    fn handle(&mut self, method: &Method, req: &ReceivedRequest) -> (u16, Value) {
+        let path = req.path.trim_start_matches('/').replace("%2f", "/");
+
+        if let Some(rest) = path.strip_prefix("-/package/") {
+            let (name, tag) = match rest.split_once("/dist-tags/") {
+                Some(v) => v,
+                None => return not_found(),
+            };
+            if *method != Method::Put || req.authorization.is_none() {
+                return unauthorized();
+            }
+
+            let version = req.body.clone().unwrap_or_default();
+            return match self.packages.get_mut(name) {
+                Some(packument)
+                    if packument["versions"]
+                        .get(version.as_str().unwrap_or_default())
+                        .is_some() =>
+                {
+                    packument["dist-tags"][tag] = version;
+                    (201, json!({ "ok": true }))
+                }
+                _ => not_found(),
+            };
+        }
+
+        match *method {
+            Method::Get => match self.packages.get(&path) {
+                Some(packument) => (200, packument.clone()),
+                None => not_found(),
+            },
+            Method::Put => {
+                if req.authorization.is_none() {
+                    return unauthorized();
+                }
+
+                let doc = req.body.clone().unwrap_or_default();
+                let packument = self
+                    .packages
+                    .entry(path.clone())
+                    .or_insert_with(|| json!({ "name": path, "dist-tags": {}, "versions": {} }));
+
+                for (version, manifest) in doc["versions"].as_object().into_iter().flatten() {
+                    if packument["versions"].get(version).is_some() {
+                        return (
+                            403,
+                            json!({
+                                "error":
+                                    format!(
+                                        "You cannot publish over the previously published \
+                                         versions: {}.",
+                                        version
+                                    )
+                            }),
+                        );
+                    }
+                    packument["versions"][version] = manifest.clone();
+                }
+                for (tag, version) in doc["dist-tags"].as_object().into_iter().flatten() {
+                    packument["dist-tags"][tag] = version.clone();
+                }
+
+                (201, json!({ "ok": true }))
+            }
+            _ => not_found(),
+        }
+    }
+}
+
+fn header(req: &Request, name: &'static str) -> Option<String> {
+    req.headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.to_string())
+}
+
+fn not_found() -> (u16, Value) {
+    (404, json!({ "error": "Not found" }))
+}
+
+fn unauthorized() -> (u16, Value) {
+    (
+        401,
+        json!({ "error": "You must be logged in to publish packages." }),
+    )
+}
+
+/// Creates a package file of `name@version` in `dir`.
+pub(crate) fn create_tarball(dir: &Path, name: &str, version: &str, content: &str) -> NpmTarball {
+    let pkg_dir = dir.join(format!("{}-{}", name.replace('/', "-"), version));
+    create_dir_all(&pkg_dir).unwrap();
+    write(
+        pkg_dir.join("package.json"),
+        json!({ "name": name, "version": version, "description": "A test package" }).to_string(),
+    )
+    .unwrap();
+    write(pkg_dir.join("index.js"), content).unwrap();
+
+    create_npm_package(&pkg_dir).unwrap()
+}
@@ -0,0 +1,479 @@
+use super::platform::all_node_platforms;
+use crate::util::config::NamingConfig;
+use anyhow::{bail, Context, Result};
+use cargo_metadata::Version;
+use serde_json::Value;
+use std::{fmt, fs::read_to_string, path::Path};
+use tracing::{error, warn};
+
+/// Maximum length of a package name, enforced by the npm registry.
+const MAX_NAME_LEN: usize = 214;
+
+/// Commonly used license identifiers from <https://spdx.org/licenses/>.
+///
+/// Other identifiers are only warned, because this is not the full list.
+const SPDX_LICENSES: &[&str] = &[
+    "0BSD",
+    "AFL-3.0",
+    "AGPL-3.0",
+    "AGPL-3.0-only",
+    "AGPL-3.0-or-later",
+    "Apache-1.1",
+    "Apache-2.0",
+    "Artistic-2.0",
+    "BlueOak-1.0.0",
+    "BSD-1-Clause",
+    "BSD-2-Clause",
+    "BSD-2-Clause-Patent",
+    "BSD-3-Clause",
+    "BSD-3-Clause-Clear",
+    "BSD-4-Clause",
+    "BSL-1.0",
+    "CC-BY-3.0",
+    "CC-BY-4.0",
+    "CC-BY-SA-4.0",
+    "CC0-1.0",
+    "CDDL-1.0",
+    "CDDL-1.1",
+    "CPL-1.0",
+    "ECL-2.0",
+    "EPL-1.0",
+    "EPL-2.0",
+    "EUPL-1.1",
+    "EUPL-1.2",
+    "GPL-2.0",
+    "GPL-2.0-only",
+    "GPL-2.0-or-later",
+    "GPL-3.0",
+    "GPL-3.0-only",
+    "GPL-3.0-or-later",
+    "ISC",
+    "LGPL-2.0",
+    "LGPL-2.0-only",
+    "LGPL-2.0-or-later",
+    "LGPL-2.1",
+    "LGPL-2.1-only",
+    "LGPL-2.1-or-later",
+    "LGPL-3.0",
+    "LGPL-3.0-only",
+    "LGPL-3.0-or-later",
+    "MIT",
+    "MIT-0",
+    "MPL-1.1",
+    "MPL-2.0",
+    "MPL-2.0-no-copyleft-exception",
+    "MS-PL",
+    "MS-RL",
+    "NCSA",
+    "OFL-1.1",
+    "OpenSSL",
+    "OSL-3.0",
+    "PostgreSQL",
+    "Python-2.0",
+    "Unicode-DFS-2016",
+    "Unlicense",
+    "UPL-1.0",
+    "W3C",
+    "WTFPL",
+    "X11",
+    "Zlib",
+];
+
+/// Commonly used license exceptions, which are allowed after `WITH`.
+const SPDX_EXCEPTIONS: &[&str] = &[
+    "Classpath-exception-2.0",
+    "GCC-exception-3.1",
+    "LLVM-exception",
+    "Autoconf-exception-3.0",
+    "Bison-exception-2.2",
+];
+
+/// A problem of a `package.json` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    /// JSON path of the field, like `$.repository.url`.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Reads and validates the `package.json` of `crate_name` at `path`.
+///
+/// All problems are logged before returning an error.
+pub fn check_package_json(path: &Path, crate_name: &str, naming: &NamingConfig) -> Result<Value> {
+    let s = read_to_string(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+    let json: Value = serde_json::from_str(&s)
+        .with_context(|| format!("failed to parse `{}`", path.display()))?;
+
+    let problems = validate_package_json(&json, crate_name, naming);
+    if !problems.is_empty() {
+        for problem in &problems {
+            error!("{}: {}", path.display(), problem);
+        }
+
+        bail!(
+            "`{}` has {} problem(s); see the log above",
+            path.display(),
+            problems.len()
+        )
+    }
+
+    Ok(json)
+}
+
+/// Validates a `package.json` file for a plugin.
+pub fn validate_package_json(
+    json: &Value,
+    crate_name: &str,
+    naming: &NamingConfig,
+) -> Vec<Problem> {
+    let mut problems = vec![];
+    let mut report = |path: &str, message: String| {
+        problems.push(Problem {
+            path: path.to_string(),
+            message,
+        })
+    };
+
+    let obj = match json.as_object() {
+        Some(v) => v,
+        None => {
+            report("$", "package.json should be an object".into());
+            return problems;
+        }
+    };
+
+    match obj.get("name") {
+        Some(Value::String(name)) => {
+            if let Err(msg) = validate_name(name) {
+                report("$.name", msg);
+            } else {
+                let longest = all_node_platforms()
+                    .iter()
+                    .map(|p| naming.package_name(name, crate_name, p))
+                    .max_by_key(|name| name.len());
+
+                if let Some(longest) = longest {
+                    if longest.len() > MAX_NAME_LEN {
+                        report(
+                            "$.name",
+                            format!(
+                                "name is too long. The name of a platform package (`{}`) has {} \
+                                 characters",
+                                longest,
+                                longest.len()
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+        Some(_) => report("$.name", "name should be a string".into()),
+        None => report("$.name", "name is required".into()),
+    }
+
+    match obj.get("version") {
+        Some(Value::String(version)) => {
+            if let Err(err) = Version::parse(version) {
+                report(
+                    "$.version",
+                    format!("`{}` is not a valid semver version: {}", version, err),
+                );
+            }
+        }
+        Some(_) => report("$.version", "version should be a string".into()),
+        None => report("$.version", "version is required".into()),
+    }
+
+    match obj.get("license") {
+        Some(Value::String(license)) => match validate_license(license) {
+            Ok(unknown) => {
+                for id in unknown {
+                    warn!(
+                        "$.license: `{}` is not a known SPDX license identifier. See \
+                         https://spdx.org/licenses/",
+                        id
+                    );
+                }
+            }
+            Err(msg) => report("$.license", msg),
+        },
+        Some(_) => report(
+            "$.license",
+            "license should be a string containing a SPDX license expression".into(),
+        ),
+        None => report("$.license", "license is required".into()),
+    }
+
+    match obj.get("repository") {
+        None => {}
+        Some(Value::String(s)) => {
+            if s.trim().is_empty() {
+                report("$.repository", "repository should not be empty".into());
+            }
+        }
+        Some(Value::Object(repo)) => {
+            for (key, required) in &[("type", true), ("url", true), ("directory", false)] {
+                match repo.get(*key) {
+                    Some(Value::String(_)) => {}
+                    Some(_) => report(
+                        &format!("$.repository.{}", key),
+                        format!("{} should be a string", key),
+                    ),
+                    None if *required => report(
+                        &format!("$.repository.{}", key),
+                        format!("{} is required", key),
+                    ),
+                    None => {}
+                }
+            }
+        }
+        Some(_) => report(
+            "$.repository",
+            "repository should be a string or an object with `type` and `url`".into(),
+        ),
+    }
+
+    problems
+}
+
+/// Rules of https://github.com/npm/validate-npm-package-name
+fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("name should not be empty".into());
+    }
+
+    if name.len() > MAX_NAME_LEN {
+        return Err(format!(
+            "name should not be longer than {} characters",
+            MAX_NAME_LEN
+        ));
+    }
+
+    if name.trim() != name {
+        return Err("name should not have leading or trailing spaces".into());
+    }
+
+    if name.to_lowercase() != name {
+        return Err("name should not contain uppercase letters".into());
+    }
+
+    let pkg_name = match name.strip_prefix('@') {
+        Some(scoped) => {
+            let (scope, pkg_name) = scoped
+                .split_once('/')
+                .ok_or_else(|| "scoped name should be like `@scope/name`".to_string())?;
+            validate_name_part(scope).map_err(|msg| format!("scope {}", msg))?;
+            pkg_name
+        }
+        None => name,
+    };
+
+    if pkg_name == "node_modules" || pkg_name == "favicon.ico" {
+        return Err(format!("`{}` is not allowed as a name", pkg_name));
+    }
+
+    validate_name_part(pkg_name).map_err(|msg| format!("name {}", msg))
+}
+
+fn validate_name_part(s: &str) -> Result<(), String> {
+    if s.is_empty() {
+        return Err("should not be empty".into());
+    }
+
+    if s.starts_with('.') || s.starts_with('_') {
+        return Err("should not start with `.` or `_`".into());
+    }
+
+    if let Some(c) = s
+        .chars()
+        .find(|&c| !(c.is_ascii_lowercase() || c.is_ascii_digit() || "-._".contains(c)))
+    {
+        return Err(format!("should not contain `{}`", c));
+    }
+
+    Ok(())
+}
+
+/// Validates the syntax of a SPDX license expression.
+///
+/// Returns identifiers which are not in the list of commonly used ones.
+fn validate_license(license: &str) -> Result<Vec<String>, String> {
+    if license == "UNLICENSED" || license.starts_with("SEE LICENSE IN ") {
+        return Ok(vec![]);
+    }
+
+    let tokens = license
+        .replace('(', " ( ")
+        .replace(')', " ) ")
+        .split_whitespace()
+        .map(String::from)
+        .collect::<Vec<_>>();
+
+    if tokens.is_empty() {
+        return Err("license should not be empty".into());
+    }
+
+    let mut depth = 0i32;
+    let mut expect_license = true;
+    let mut after_with = false;
+    let mut unknown = vec![];
+
+    for token in &tokens {
+        match &**token {
+            "(" if expect_license => depth += 1,
+            ")" if !expect_license => {
+                depth -= 1;
+                if depth < 0 {
+                    return Err(format!("`{}` has unbalanced parentheses", license));
+                }
+            }
+            "AND" | "OR" if !expect_license => expect_license = true,
+            "WITH" if !expect_license => {
+                expect_license = true;
+                after_with = true;
+            }
+            id if expect_license => {
+                let bare = id.strip_suffix('+').unwrap_or(id);
+                if bare.is_empty()
+                    || !bare
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == ':')
+                {
+                    return Err(format!(
+                        "`{}` is not a SPDX license identifier. See https://spdx.org/licenses/",
+                        id
+                    ));
+                }
+
+                let known = if after_with {
+                    SPDX_EXCEPTIONS.contains(&id)
+                } else {
+                    SPDX_LICENSES.contains(&bare)
+                        || bare.starts_with("LicenseRef-")
+                        || bare.starts_with("DocumentRef-")
+                };
+                if !known {
+                    unknown.push(id.to_string());
+                }
+
+                expect_license = false;
+                after_with = false;
+            }
+            _ => {
+                return Err(format!(
+                    "`{}` is not a valid SPDX license expression (unexpected `{}`)",
+                    license, token
+                ))
+            }
+        }
+    }
+
+    if expect_license || depth != 0 {
+        return Err(format!(
+            "`{}` is not a valid SPDX license expression",
+            license
+        ));
+    }
+
+    Ok(unknown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_names() {
+        for name in &[
+            "plugin",
+            "swc-plugin.example_1",
+            "@swc/plugin-example",
+            "@my-org/plugin",
+        ] {
+            assert_eq!(validate_name(name), Ok(()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn invalid_names() {
+        for name in &[
+            "",
+            " plugin",
+            "Plugin",
+            ".plugin",
+            "_plugin",
+            "node_modules",
+            "favicon.ico",
+            "plugin~1",
+            "@scope",
+            "@/plugin",
+            "@scope/",
+            "@_scope/plugin",
+            "@scope/plugin/extra",
+        ] {
+            assert!(validate_name(name).is_err(), "{:?}", name);
+        }
+
+        assert!(validate_name(&"a".repeat(MAX_NAME_LEN)).is_ok());
+        assert!(validate_name(&"a".repeat(MAX_NAME_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn valid_licenses() {
+        for license in &[
+            "MIT",
+            "Apache-2.0 OR MIT",
+            "(MIT OR Apache-2.0) AND BSD-3-Clause",
+            "GPL-2.0+",
+            "Apache-2.0 WITH LLVM-exception",
+            "LicenseRef-Proprietary",
+            "UNLICENSED",
+            "SEE LICENSE IN LICENSE.txt",
+        ] {
+            assert_eq!(validate_license(license), Ok(vec![]), "{}", license);
+        }
+    }
+
+    #[test]
+    fn unknown_licenses_are_not_errors() {
+        assert_eq!(
+            validate_license("MPL-1.0 OR Unicode-3.0"),
+            Ok(vec!["MPL-1.0".to_string(), "Unicode-3.0".to_string()])
+        );
+        assert_eq!(
+            validate_license("(CC-BY-NC-4.0 AND BSD-3-Clause-LBNL)"),
+            Ok(vec![
+                "CC-BY-NC-4.0".to_string(),
+                "BSD-3-Clause-LBNL".to_string()
+            ])
+        );
+        assert_eq!(
+            validate_license("GPL-3.0 WITH Some-exception"),
+            Ok(vec!["Some-exception".to_string()])
+        );
+    }
+
+    #[test]
+    fn invalid_licenses() {
+        for license in &[
+            "",
+            "MIT OR",
+            "OR MIT",
+            "MIT Apache-2.0",
+            "(MIT",
+            "MIT)",
+            "MIT AND (Apache-2.0",
+            "WITH LLVM-exception",
+            "MIT/Apache-2.0",
+            "MIT, Apache-2.0",
+        ] {
+            assert!(validate_license(license).is_err(), "{:?}", license);
+        }
+    }
+}